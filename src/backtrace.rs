@@ -0,0 +1,67 @@
+use std::mem::size_of;
+
+use libc::pid_t;
+
+use crate::{
+    error::Result,
+    maps::read_maps,
+    mem::read_memory,
+    regs::{abi, get_regs, Abi},
+};
+
+/// Walks the saved frame-pointer chain of a stopped tracee and returns the
+/// current `rip` followed by up to `max_frames - 1` return addresses. Only
+/// meaningful for code built with frame pointers. The walk ends at a null,
+/// unaligned or unmapped `rbp`, or one that doesn't move up the stack. Frames
+/// of an i386 tracee are walked the same way, through `ebp` with 4-byte
+/// slots.
+pub fn backtrace_fp(pid: pid_t, max_frames: usize) -> Result<Vec<u64>> {
+    let word = match abi(pid)? {
+        Abi::X86_64 => size_of::<u64>(),
+        Abi::I386 => size_of::<u32>(),
+    };
+    let regs = get_regs(pid)?;
+    let maps = read_maps(pid)?;
+    let mut frames = Vec::with_capacity(max_frames);
+    if max_frames == 0 {
+        return Ok(frames);
+    }
+    frames.push(regs.rip);
+
+    let mut rbp = regs.rbp;
+    while frames.len() < max_frames {
+        if rbp == 0 || rbp % word as u64 != 0 {
+            break;
+        }
+        if !maps.iter().any(|region| region.contains(rbp)) {
+            break;
+        }
+
+        // [rbp] holds the caller's rbp, [rbp + word] the return address.
+        let mut frame = [0; 2 * size_of::<u64>()];
+        let frame = &mut frame[..2 * word];
+        if read_memory(pid, rbp, frame)? < frame.len() {
+            break;
+        }
+        let (next, ret) = frame.split_at(word);
+        let (next, ret) = (read_word(next), read_word(ret));
+        if ret == 0 {
+            break;
+        }
+        frames.push(ret);
+
+        if next <= rbp {
+            break;
+        }
+        rbp = next;
+    }
+
+    Ok(frames)
+}
+
+/// Zero-extends a little-endian word of up to 8 bytes.
+fn read_word(bytes: &[u8]) -> u64 {
+    let mut word = [0; size_of::<u64>()];
+    word[..bytes.len()].copy_from_slice(bytes);
+    u64::from_le_bytes(word)
+}
@@ -0,0 +1,324 @@
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fmt,
+};
+
+use libc::{c_int, c_uint, pid_t, AT_ENTRY, SIGTRAP, WIFSTOPPED, WSTOPSIG};
+
+use crate::{
+    error::{Error, Result},
+    mem::{read_memory, write_memory},
+    proc::read_auxv,
+    ptrace::{cont, handle_signal, ptrace_event, step_until},
+    regs::{get_regs, set_regs},
+    tracee::Tracee,
+};
+
+const INT3: u8 = 0xcc;
+
+/// Patches an `int3` over the byte at `addr` and returns the original byte.
+/// Goes through `PTRACE_POKEDATA`, so it works on read-only code pages.
+pub fn set_breakpoint(pid: pid_t, addr: u64) -> Result<u8> {
+    let mut original = [0; 1];
+    read_memory(pid, addr, &mut original)?;
+    write_memory(pid, addr, &[INT3])?;
+    Ok(original[0])
+}
+
+pub fn clear_breakpoint(pid: pid_t, addr: u64, original: u8) -> Result<()> {
+    write_memory(pid, addr, &[original])?;
+    Ok(())
+}
+
+/// Executes the instruction under the breakpoint at `addr` and re-arms it:
+/// restores the original byte, rewinds `rip` onto `addr`, single-steps and
+/// patches the `int3` back in. The tracee is left stopped after the step.
+/// A pending signal is delivered with the step, as by [`step_until`]; if it
+/// enters a handler, the instruction runs once the handler returns, into the
+/// re-armed breakpoint. Fails without re-arming if the tracee exits during
+/// the step, and with the breakpoint re-armed and the status in
+/// [`Error::UnexpectedStatus`] if some other stop, such as a ptrace event,
+/// preempts the step.
+pub fn step_over_breakpoint(
+    pid: pid_t,
+    addr: u64,
+    original: u8,
+    attach: c_uint,
+    options: c_int,
+) -> Result<u8> {
+    clear_breakpoint(pid, addr, original)?;
+
+    let mut regs = get_regs(pid)?;
+    regs.rip = addr;
+    set_regs(pid, &regs)?;
+
+    let status = step_until(pid, attach, options)?;
+    if !WIFSTOPPED(status) {
+        return Err(Error::UnexpectedStatus(status));
+    }
+
+    let original = set_breakpoint(pid, addr)?;
+    if WSTOPSIG(status) != SIGTRAP || ptrace_event(status) != 0 {
+        return Err(Error::UnexpectedStatus(status));
+    }
+
+    Ok(original)
+}
+
+/// Runs the tracee until it reaches `addr` and returns the status of the stop.
+/// A software breakpoint is placed at `addr` for the run and removed again on
+/// the stop, with `rip` rewound onto it if it was hit. If some other stop
+/// comes first, its status is returned all the same, with the breakpoint
+/// likewise removed unless an exec has already discarded it along with the
+/// old image. A tracee already at `addr` stops there again right away, so
+/// step it off first.
+pub fn run_to(pid: pid_t, addr: u64, attach: c_uint, options: c_int) -> Result<c_int> {
+    run_to_hit(pid, addr, attach, options).map(|(status, _)| status)
+}
+
+fn run_to_hit(pid: pid_t, addr: u64, attach: c_uint, options: c_int) -> Result<(c_int, bool)> {
+    let original = set_breakpoint(pid, addr)?;
+
+    cont(pid, 0)?;
+    let status = handle_signal(pid, attach, options)?;
+    if !WIFSTOPPED(status) {
+        return Ok((status, false));
+    }
+
+    // After an exec, the address may be unmapped or hold some other code.
+    let mut byte = [0; 1];
+    if read_memory(pid, addr, &mut byte).is_ok() && byte[0] == INT3 {
+        clear_breakpoint(pid, addr, original)?;
+    }
+
+    let mut regs = get_regs(pid)?;
+    if WSTOPSIG(status) != SIGTRAP || ptrace_event(status) != 0 || regs.rip != addr + 1 {
+        return Ok((status, false));
+    }
+
+    regs.rip = addr;
+    set_regs(pid, &regs)?;
+    Ok((status, true))
+}
+
+/// Runs a tracee that has just exec'd up to its entry point, ahead of any of
+/// the program's own code but after the dynamic loader, and returns the
+/// entry address. This is [`run_to`] `AT_ENTRY`, which already accounts for
+/// where a PIE was loaded, so the tracee is left stopped at the entry with
+/// its code intact. Fails with the breakpoint removed if some other stop
+/// comes first.
+pub fn break_at_entry(pid: pid_t, attach: c_uint, options: c_int) -> Result<u64> {
+    let entry = *read_auxv(pid)?
+        .get(&AT_ENTRY)
+        .ok_or_else(|| Error::Malformed(format!("/proc/{pid}/auxv")))?;
+
+    let (status, hit) = run_to_hit(pid, entry, attach, options)?;
+    if !hit {
+        return Err(Error::UnexpectedStatus(status));
+    }
+
+    Ok(entry)
+}
+
+/// Predicate evaluated with the tracee stopped at a conditional breakpoint.
+pub type Condition = Box<dyn FnMut(&Tracee) -> Result<bool>>;
+
+#[derive(Default)]
+pub struct Breakpoints {
+    originals: HashMap<u64, u8>,
+    hits: HashMap<u64, u64>,
+    // Hit count up to which a breakpoint doesn't stop.
+    ignore_until: HashMap<u64, u64>,
+    conditions: HashMap<u64, Condition>,
+    temporary: HashSet<u64>,
+}
+
+impl fmt::Debug for Breakpoints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Breakpoints")
+            .field("originals", &self.originals)
+            .field("hits", &self.hits)
+            .field("ignore_until", &self.ignore_until)
+            .field("conditions", &self.conditions.keys().collect::<Vec<_>>())
+            .field("temporary", &self.temporary)
+            .finish()
+    }
+}
+
+impl Breakpoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, pid: pid_t, addr: u64) -> Result<()> {
+        if let Entry::Vacant(entry) = self.originals.entry(addr) {
+            entry.insert(set_breakpoint(pid, addr)?);
+        }
+
+        Ok(())
+    }
+
+    /// Inserts a one-shot breakpoint, as gdb's `tbreak`: once
+    /// [`Breakpoints::cont`] reports its hit, it is removed. Callers that
+    /// drive the tracee themselves remove it with [`Breakpoints::remove`]
+    /// after [`Breakpoints::adjust_rip_on_hit`].
+    pub fn insert_temporary(&mut self, pid: pid_t, addr: u64) -> Result<()> {
+        self.insert(pid, addr)?;
+        self.temporary.insert(addr);
+        Ok(())
+    }
+
+    pub fn is_temporary(&self, addr: u64) -> bool {
+        self.temporary.contains(&addr)
+    }
+
+    pub fn remove(&mut self, pid: pid_t, addr: u64) -> Result<bool> {
+        let Some(&original) = self.originals.get(&addr) else {
+            return Ok(false);
+        };
+
+        clear_breakpoint(pid, addr, original)?;
+        self.originals.remove(&addr);
+        self.hits.remove(&addr);
+        self.ignore_until.remove(&addr);
+        self.conditions.remove(&addr);
+        self.temporary.remove(&addr);
+        Ok(true)
+    }
+
+    pub fn contains(&self, addr: u64) -> bool {
+        self.originals.contains_key(&addr)
+    }
+
+    pub fn original(&self, addr: u64) -> Option<u8> {
+        self.originals.get(&addr).copied()
+    }
+
+    /// Counts a hit of the breakpoint at `addr`, to be called when its trap
+    /// fires. Addresses that aren't breakpoints are ignored.
+    pub fn record_hit(&mut self, addr: u64) {
+        if self.contains(addr) {
+            *self.hits.entry(addr).or_default() += 1;
+        }
+    }
+
+    pub fn hit_count(&self, addr: u64) -> u64 {
+        self.hits.get(&addr).copied().unwrap_or(0)
+    }
+
+    /// Makes the breakpoint at `addr` ignore its next `n` hits, as gdb's
+    /// `ignore`. Returns false if there is no breakpoint at `addr`.
+    pub fn set_ignore_count(&mut self, addr: u64, n: u64) -> bool {
+        if !self.contains(addr) {
+            return false;
+        }
+
+        self.ignore_until
+            .insert(addr, self.hit_count(addr).saturating_add(n));
+        true
+    }
+
+    /// Whether the latest recorded hit at `addr` should stop, i.e. its ignore
+    /// count has been used up.
+    pub fn should_stop(&self, addr: u64) -> bool {
+        self.ignore_until
+            .get(&addr)
+            .is_none_or(|&until| self.hit_count(addr) > until)
+    }
+
+    /// Makes the breakpoint at `addr` conditional: [`Breakpoints::cont`] only
+    /// stops there when `condition` returns true. Returns false if there is no
+    /// breakpoint at `addr`.
+    pub fn set_condition<F>(&mut self, addr: u64, condition: F) -> bool
+    where
+        F: FnMut(&Tracee) -> Result<bool> + 'static,
+    {
+        if !self.contains(addr) {
+            return false;
+        }
+
+        self.conditions.insert(addr, Box::new(condition));
+        true
+    }
+
+    pub fn clear_condition(&mut self, addr: u64) -> bool {
+        self.conditions.remove(&addr).is_some()
+    }
+
+    /// Continues the tracee until it reports a stop worth surfacing and
+    /// returns its status. A hit of a known breakpoint whose condition is
+    /// false, or whose ignore count isn't used up, is stepped over and
+    /// continued from without being reported. Only hits passing the
+    /// condition count towards [`Breakpoints::hit_count`]. When a hit is
+    /// reported, `rip` has already been rewound onto the breakpoint, and a
+    /// temporary breakpoint has been removed. A tracee stopped on a
+    /// breakpoint, such as after a reported hit, is first stepped over it,
+    /// as gdb's `continue` does, with `sig` delivered after that step. A stop
+    /// that preempts a step over, such as a ptrace event, is reported with
+    /// the breakpoint re-armed.
+    pub fn cont(&mut self, tracee: &Tracee, sig: c_int) -> Result<c_int> {
+        let pid = tracee.pid();
+        let rip = get_regs(pid)?.rip;
+        if let Some(original) = self.original(rip) {
+            if let Some(status) = self.step_over(tracee, rip, original)? {
+                return Ok(status);
+            }
+        }
+        cont(pid, sig)?;
+
+        loop {
+            let status = handle_signal(pid, tracee.attach(), tracee.options())?;
+            if !WIFSTOPPED(status) || WSTOPSIG(status) != SIGTRAP || ptrace_event(status) != 0 {
+                return Ok(status);
+            }
+
+            let Some(addr) = self.adjust_rip_on_hit(pid)? else {
+                return Ok(status);
+            };
+            let hit = match self.conditions.get_mut(&addr) {
+                Some(condition) => condition(tracee)?,
+                None => true,
+            };
+            if hit {
+                self.record_hit(addr);
+                if self.should_stop(addr) {
+                    if self.is_temporary(addr) {
+                        self.remove(pid, addr)?;
+                    }
+                    return Ok(status);
+                }
+            }
+
+            let original = self.originals[&addr];
+            if let Some(status) = self.step_over(tracee, addr, original)? {
+                return Ok(status);
+            }
+            cont(pid, 0)?;
+        }
+    }
+
+    // Returns the status of a stop that preempted the step, if any.
+    fn step_over(&self, tracee: &Tracee, addr: u64, original: u8) -> Result<Option<c_int>> {
+        let (attach, options) = (tracee.attach(), tracee.options());
+        match step_over_breakpoint(tracee.pid(), addr, original, attach, options) {
+            Ok(_) => Ok(None),
+            Err(Error::UnexpectedStatus(status)) => Ok(Some(status)),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// After `int3` executes, `rip` points one byte past the trap. If that
+    /// byte belongs to a known breakpoint, rewinds `rip` onto it and returns
+    /// the breakpoint address.
+    pub fn adjust_rip_on_hit(&self, pid: pid_t) -> Result<Option<u64>> {
+        let mut regs = get_regs(pid)?;
+        let addr = regs.rip.wrapping_sub(1);
+        if !self.contains(addr) {
+            return Ok(None);
+        }
+
+        regs.rip = addr;
+        set_regs(pid, &regs)?;
+        Ok(Some(addr))
+    }
+}
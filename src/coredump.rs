@@ -0,0 +1,244 @@
+use std::{
+    fs,
+    io::Write,
+    mem::{size_of, MaybeUninit},
+    slice,
+};
+
+use libc::{
+    c_int, pid_t, user_regs_struct, Elf64_Ehdr, Elf64_Phdr, EI_CLASS, EI_DATA, EI_VERSION,
+    ELFCLASS64, ELFDATA2LSB, ELFMAG0, ELFMAG1, ELFMAG2, ELFMAG3, EM_X86_64, ET_CORE, EV_CURRENT,
+    NT_PRPSINFO, NT_PRSTATUS, PF_R, PF_W, PF_X, PT_LOAD, PT_NOTE,
+};
+
+use crate::{
+    error::Result,
+    maps::{read_maps, MemoryRegion, Perms},
+    mem::ProcMem,
+    ptrace::get_siginfo,
+    regs::get_regs,
+};
+
+const PAGE_SIZE: u64 = 4096;
+const CHUNK_SIZE: usize = 1 << 20;
+
+#[repr(C)]
+struct ElfSiginfo {
+    si_signo: c_int,
+    si_code: c_int,
+    si_errno: c_int,
+}
+
+#[repr(C)]
+struct Timeval {
+    tv_sec: i64,
+    tv_usec: i64,
+}
+
+#[repr(C)]
+struct ElfPrstatus {
+    pr_info: ElfSiginfo,
+    pr_cursig: i16,
+    pr_sigpend: u64,
+    pr_sighold: u64,
+    pr_pid: pid_t,
+    pr_ppid: pid_t,
+    pr_pgrp: pid_t,
+    pr_sid: pid_t,
+    pr_utime: Timeval,
+    pr_stime: Timeval,
+    pr_cutime: Timeval,
+    pr_cstime: Timeval,
+    pr_reg: user_regs_struct,
+    pr_fpvalid: c_int,
+}
+
+#[repr(C)]
+struct ElfPrpsinfo {
+    pr_state: i8,
+    pr_sname: u8,
+    pr_zomb: i8,
+    pr_nice: i8,
+    pr_flag: u64,
+    pr_uid: u32,
+    pr_gid: u32,
+    pr_pid: pid_t,
+    pr_ppid: pid_t,
+    pr_pgrp: pid_t,
+    pr_sid: pid_t,
+    pr_fname: [u8; 16],
+    pr_psargs: [u8; 80],
+}
+
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { slice::from_raw_parts((value as *const T).cast::<u8>(), size_of::<T>()) }
+}
+
+fn zeroed<T>() -> T {
+    unsafe { MaybeUninit::<T>::zeroed().assume_init() }
+}
+
+fn align4(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn push_note(notes: &mut Vec<u8>, kind: c_int, desc: &[u8]) {
+    const NAME: &[u8] = b"CORE\0";
+
+    notes.extend_from_slice(&(NAME.len() as u32).to_ne_bytes());
+    notes.extend_from_slice(&(desc.len() as u32).to_ne_bytes());
+    notes.extend_from_slice(&(kind as u32).to_ne_bytes());
+    notes.extend_from_slice(NAME);
+    notes.resize(align4(notes.len()), 0);
+    notes.extend_from_slice(desc);
+    notes.resize(align4(notes.len()), 0);
+}
+
+fn copy_truncated(dst: &mut [u8], src: &[u8]) {
+    // Leave room for the NUL terminator.
+    let len = src.len().min(dst.len() - 1);
+    dst[..len].copy_from_slice(&src[..len]);
+}
+
+fn build_notes(pid: pid_t) -> Result<Vec<u8>> {
+    let signal = get_siginfo(pid).map_or(0, |siginfo| siginfo.si_signo);
+
+    // Fields 4-6 of /proc/<pid>/stat, after the parenthesised comm.
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat"))?;
+    let mut fields = stat
+        .rsplit_once(')')
+        .map_or("", |(_, rest)| rest)
+        .split_whitespace()
+        .skip(1)
+        .map(|field| field.parse().unwrap_or(0));
+    let (ppid, pgrp, sid) = (
+        fields.next().unwrap_or(0),
+        fields.next().unwrap_or(0),
+        fields.next().unwrap_or(0),
+    );
+
+    let mut prstatus = zeroed::<ElfPrstatus>();
+    prstatus.pr_info.si_signo = signal;
+    prstatus.pr_cursig = signal as i16;
+    prstatus.pr_pid = pid;
+    prstatus.pr_ppid = ppid;
+    prstatus.pr_pgrp = pgrp;
+    prstatus.pr_sid = sid;
+    prstatus.pr_reg = get_regs(pid)?;
+
+    let mut prpsinfo = zeroed::<ElfPrpsinfo>();
+    prpsinfo.pr_sname = b't';
+    prpsinfo.pr_pid = pid;
+    prpsinfo.pr_ppid = ppid;
+    prpsinfo.pr_pgrp = pgrp;
+    prpsinfo.pr_sid = sid;
+    let comm = fs::read(format!("/proc/{pid}/comm"))?;
+    copy_truncated(&mut prpsinfo.pr_fname, comm.trim_ascii_end());
+    let mut cmdline = fs::read(format!("/proc/{pid}/cmdline"))?;
+    cmdline
+        .iter_mut()
+        .for_each(|byte| *byte = if *byte == 0 { b' ' } else { *byte });
+    copy_truncated(&mut prpsinfo.pr_psargs, cmdline.trim_ascii_end());
+
+    let mut notes = Vec::new();
+    push_note(&mut notes, NT_PRSTATUS, as_bytes(&prstatus));
+    push_note(&mut notes, NT_PRPSINFO, as_bytes(&prpsinfo));
+    Ok(notes)
+}
+
+fn is_dumped(region: &MemoryRegion) -> bool {
+    // [vvar] and [vsyscall] can't be read through /proc/<pid>/mem.
+    region.perms.contains(Perms::READ)
+        && !region
+            .path
+            .as_ref()
+            .is_some_and(|path| path.starts_with("[vvar") || path.starts_with("[vsyscall]"))
+}
+
+fn segment_flags(perms: Perms) -> u32 {
+    let mut flags = 0;
+    if perms.contains(Perms::READ) {
+        flags |= PF_R;
+    }
+    if perms.contains(Perms::WRITE) {
+        flags |= PF_W;
+    }
+    if perms.contains(Perms::EXEC) {
+        flags |= PF_X;
+    }
+
+    flags
+}
+
+/// Writes an ELF core file of a stopped tracee to `out`: a `PT_NOTE` segment
+/// with its `NT_PRSTATUS` and `NT_PRPSINFO`, and a `PT_LOAD` segment per
+/// mapping. Only the thread `pid` is recorded. Mappings without read
+/// permission are described but not dumped, and pages that turn out to be
+/// unreadable are written as zeros.
+pub fn core_dump(pid: pid_t, out: &mut impl Write) -> Result<()> {
+    let regions = read_maps(pid)?;
+    let notes = build_notes(pid)?;
+    let mem = ProcMem::open(pid)?;
+
+    let phnum = regions.len() + 1;
+    let notes_offset = size_of::<Elf64_Ehdr>() + phnum * size_of::<Elf64_Phdr>();
+    let data_offset = (notes_offset + notes.len()) as u64;
+    let data_offset = (data_offset + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+
+    let mut ehdr = zeroed::<Elf64_Ehdr>();
+    ehdr.e_ident[..4].copy_from_slice(&[ELFMAG0, ELFMAG1, ELFMAG2, ELFMAG3]);
+    ehdr.e_ident[EI_CLASS] = ELFCLASS64;
+    ehdr.e_ident[EI_DATA] = ELFDATA2LSB;
+    ehdr.e_ident[EI_VERSION] = EV_CURRENT as u8;
+    ehdr.e_type = ET_CORE;
+    ehdr.e_machine = EM_X86_64;
+    ehdr.e_version = EV_CURRENT;
+    ehdr.e_phoff = size_of::<Elf64_Ehdr>() as u64;
+    ehdr.e_ehsize = size_of::<Elf64_Ehdr>() as u16;
+    ehdr.e_phentsize = size_of::<Elf64_Phdr>() as u16;
+    ehdr.e_phnum = phnum as u16;
+    out.write_all(as_bytes(&ehdr))?;
+
+    let mut note = zeroed::<Elf64_Phdr>();
+    note.p_type = PT_NOTE;
+    note.p_offset = notes_offset as u64;
+    note.p_filesz = notes.len() as u64;
+    note.p_align = 4;
+    out.write_all(as_bytes(&note))?;
+
+    let mut offset = data_offset;
+    for region in &regions {
+        let size = region.end - region.start;
+        let mut load = zeroed::<Elf64_Phdr>();
+        load.p_type = PT_LOAD;
+        load.p_flags = segment_flags(region.perms);
+        load.p_offset = offset;
+        load.p_vaddr = region.start;
+        load.p_filesz = if is_dumped(region) { size } else { 0 };
+        load.p_memsz = size;
+        load.p_align = PAGE_SIZE;
+        out.write_all(as_bytes(&load))?;
+        offset += load.p_filesz;
+    }
+
+    out.write_all(&notes)?;
+    let padding = data_offset as usize - notes_offset - notes.len();
+    out.write_all(&vec![0; padding])?;
+
+    let mut buf = vec![0; CHUNK_SIZE];
+    for region in regions.iter().filter(|region| is_dumped(region)) {
+        let mut addr = region.start;
+        while addr < region.end {
+            let len = ((region.end - addr) as usize).min(CHUNK_SIZE);
+            let mut read = mem.read(addr, &mut buf[..len]).unwrap_or(0);
+            if read == 0 {
+                read = ((PAGE_SIZE - addr % PAGE_SIZE) as usize).min(len);
+                buf[..read].fill(0);
+            }
+            out.write_all(&buf[..read])?;
+            addr += read as u64;
+        }
+    }
+
+    Ok(())
+}
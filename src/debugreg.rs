@@ -0,0 +1,128 @@
+use std::mem::{offset_of, size_of};
+
+use libc::{c_long, pid_t, user};
+
+use crate::{
+    error::{Error, Result},
+    ptrace::{peek_user, poke_user},
+};
+
+const SLOTS: u8 = 4;
+const DR6: usize = 6;
+const DR7: usize = 7;
+
+fn debugreg_offset(index: usize) -> usize {
+    offset_of!(user, u_debugreg) + index * size_of::<u64>()
+}
+
+fn check_slot(slot: u8) -> Result<()> {
+    if slot >= SLOTS {
+        return Err(Error::InvalidDebugRegSlot(slot));
+    }
+
+    Ok(())
+}
+
+/// The local-enable bit plus the R/W and LEN fields of `slot` in DR7.
+fn dr7_mask(slot: u8) -> u64 {
+    let slot = u64::from(slot);
+    (1 << (2 * slot)) | (0b1111 << (16 + 4 * slot))
+}
+
+/// Programs DR`slot` with `addr` and enables it in DR7 with the given R/W and
+/// LEN encodings.
+fn set_debugreg(pid: pid_t, slot: u8, addr: u64, rw: u64, len: u64) -> Result<()> {
+    check_slot(slot)?;
+    // The address has to be in place before DR7 enables the slot.
+    poke_user(pid, debugreg_offset(slot.into()), addr as c_long)?;
+
+    let shift = 16 + 4 * u64::from(slot);
+    let mut dr7 = peek_user(pid, debugreg_offset(DR7))? as u64;
+    dr7 &= !dr7_mask(slot);
+    dr7 |= (1 << (2 * u64::from(slot))) | (rw << shift) | (len << (shift + 2));
+    poke_user(pid, debugreg_offset(DR7), dr7 as c_long)
+}
+
+/// Sets an execute breakpoint at `addr` in debug register `slot` (0-3). The
+/// tracee stops with `SIGTRAP` before executing the instruction, without its
+/// code being patched.
+pub fn set_hw_breakpoint(pid: pid_t, slot: u8, addr: u64) -> Result<()> {
+    set_debugreg(pid, slot, addr, 0b00, 0b00)
+}
+
+pub fn clear_hw_breakpoint(pid: pid_t, slot: u8) -> Result<()> {
+    check_slot(slot)?;
+    let dr7 = peek_user(pid, debugreg_offset(DR7))? as u64;
+    poke_user(pid, debugreg_offset(DR7), (dr7 & !dr7_mask(slot)) as c_long)?;
+    poke_user(pid, debugreg_offset(slot.into()), 0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchLen {
+    One,
+    Two,
+    Four,
+    Eight,
+}
+
+impl WatchLen {
+    fn bits(self) -> u64 {
+        match self {
+            Self::One => 0b00,
+            Self::Two => 0b01,
+            Self::Four => 0b11,
+            Self::Eight => 0b10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Write,
+    ReadWrite,
+}
+
+impl WatchKind {
+    fn bits(self) -> u64 {
+        match self {
+            Self::Write => 0b01,
+            Self::ReadWrite => 0b11,
+        }
+    }
+}
+
+/// Sets a data watchpoint on the `len` bytes at `addr`, which must be
+/// aligned to `len`. The tracee stops with `SIGTRAP` after the accessing
+/// instruction; clear it with [`clear_hw_breakpoint`].
+pub fn set_watchpoint(
+    pid: pid_t,
+    slot: u8,
+    addr: u64,
+    len: WatchLen,
+    kind: WatchKind,
+) -> Result<()> {
+    set_debugreg(pid, slot, addr, kind.bits(), len.bits())
+}
+
+pub fn get_dr6(pid: pid_t) -> Result<u64> {
+    Ok(peek_user(pid, debugreg_offset(DR6))? as u64)
+}
+
+/// Returns the slot whose condition caused the last debug trap, according to
+/// the B0-B3 bits of DR6.
+pub fn hit_slot(pid: pid_t) -> Result<Option<u8>> {
+    let dr6 = get_dr6(pid)?;
+    Ok((0..SLOTS).find(|&slot| dr6 & (1 << slot) != 0))
+}
+
+/// Returns the B0-B3 bits of DR6, one per slot whose condition was met since
+/// DR6 was last cleared.
+pub fn debug_status(pid: pid_t) -> Result<u8> {
+    Ok((get_dr6(pid)? & ((1 << SLOTS) - 1)) as u8)
+}
+
+/// Zeroes DR6, so the status bits of the next debug trap aren't mixed up
+/// with those of the previous ones.
+pub fn clear_debug_status(pid: pid_t) -> Result<()> {
+    poke_user(pid, debugreg_offset(DR6), 0)
+}
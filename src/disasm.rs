@@ -0,0 +1,44 @@
+use iced_x86::{Decoder, DecoderOptions, Formatter, IntelFormatter};
+use libc::pid_t;
+
+use crate::{error::Result, mem::read_memory_vm};
+
+/// Longest possible x86 instruction.
+const MAX_INSN_LEN: usize = 15;
+
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    pub addr: u64,
+    pub len: usize,
+    pub text: String,
+}
+
+/// Decodes up to `count` instructions starting at `addr` in the tracee, in
+/// Intel syntax. Stops early at the first instruction that is invalid or
+/// runs past the bytes that could be read.
+pub fn disasm(pid: pid_t, addr: u64, count: usize) -> Result<Vec<Instruction>> {
+    let mut buf = vec![0; count.saturating_mul(MAX_INSN_LEN)];
+    let read = read_memory_vm(pid, addr, &mut buf)?;
+    buf.truncate(read);
+
+    let mut decoder = Decoder::with_ip(64, &buf, addr, DecoderOptions::NONE);
+    let mut formatter = IntelFormatter::new();
+    let mut insns = Vec::with_capacity(count);
+
+    while insns.len() < count && decoder.can_decode() {
+        let insn = decoder.decode();
+        if insn.is_invalid() {
+            break;
+        }
+
+        let mut text = String::new();
+        formatter.format(&insn, &mut text);
+        insns.push(Instruction {
+            addr: insn.ip(),
+            len: insn.len(),
+            text,
+        });
+    }
+
+    Ok(insns)
+}
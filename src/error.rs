@@ -0,0 +1,135 @@
+use std::{fmt, io};
+
+use libc::{c_int, pid_t, EINVAL, EPERM, ESRCH, PTRACE_O_SUSPEND_SECCOMP};
+
+#[derive(Debug)]
+pub enum Error {
+    Ptrace {
+        op: &'static str,
+        source: io::Error,
+    },
+    Wait(io::Error),
+    Io(io::Error),
+    UnexpectedStatus(c_int),
+    UnexpectedRegsetSize(usize),
+    ExecFailed,
+    Malformed(String),
+    InvalidDebugRegSlot(u8),
+    TooManyArguments(usize),
+    /// The operation isn't implemented for the tracee's ABI.
+    #[cfg(target_arch = "x86_64")]
+    UnsupportedAbi(crate::regs::Abi),
+    /// `ESRCH`: either the process is gone, typically because it exited
+    /// before the request reached it, or it is alive but not a tracee of the
+    /// caller in ptrace-stop, e.g. because it is still running. `kill(pid, 0)`
+    /// tells the two apart.
+    NoSuchProcess,
+    /// `PTRACE_SETOPTIONS` refused `PTRACE_O_SUSPEND_SECCOMP`, with `EPERM`
+    /// for lack of privilege or `EINVAL` where the kernel doesn't support it.
+    SuspendSeccompDenied(io::Error),
+    /// A bounded wait resumed the tracee past its cap of unreported stops;
+    /// holds the status of the stop it was left in.
+    WatchdogExpired(c_int),
+    /// Failure of an operation on one thread among several.
+    Thread {
+        tid: pid_t,
+        source: Box<Error>,
+    },
+    #[cfg(feature = "object")]
+    Elf(object::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub(crate) fn ptrace(op: &'static str) -> Self {
+        Self::ptrace_from(op, io::Error::last_os_error())
+    }
+
+    pub(crate) fn ptrace_from(op: &'static str, source: io::Error) -> Self {
+        if source.raw_os_error() == Some(ESRCH) {
+            return Self::NoSuchProcess;
+        }
+
+        Self::Ptrace { op, source }
+    }
+
+    /// Like `ptrace`, but attributes an `EPERM` or `EINVAL` to
+    /// `PTRACE_O_SUSPEND_SECCOMP` when it was among the options.
+    pub(crate) fn set_options(options: c_int) -> Self {
+        let source = io::Error::last_os_error();
+        if options & PTRACE_O_SUSPEND_SECCOMP != 0
+            && matches!(source.raw_os_error(), Some(EPERM | EINVAL))
+        {
+            return Self::SuspendSeccompDenied(source);
+        }
+
+        Self::ptrace_from("PTRACE_SETOPTIONS", source)
+    }
+
+    pub fn raw_os_error(&self) -> Option<i32> {
+        match self {
+            Self::NoSuchProcess => Some(ESRCH),
+            Self::Thread { source, .. } => source.raw_os_error(),
+            Self::Ptrace { source, .. }
+            | Self::Wait(source)
+            | Self::Io(source)
+            | Self::SuspendSeccompDenied(source) => source.raw_os_error(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ptrace { op, source } => write!(f, "ptrace({op}) failed: {source}"),
+            Self::Wait(source) => write!(f, "waitpid failed: {source}"),
+            Self::Io(source) => source.fmt(f),
+            Self::UnexpectedStatus(status) => write!(f, "unexpected wait status {status:#x}"),
+            Self::UnexpectedRegsetSize(size) => write!(f, "unexpected regset size {size}"),
+            Self::ExecFailed => write!(f, "traced child failed to exec"),
+            Self::Malformed(line) => write!(f, "malformed /proc entry {line:?}"),
+            Self::InvalidDebugRegSlot(slot) => write!(f, "invalid debug register slot {slot}"),
+            Self::TooManyArguments(count) => write!(f, "too many arguments ({count}) for a call"),
+            #[cfg(target_arch = "x86_64")]
+            Self::UnsupportedAbi(abi) => write!(f, "unsupported tracee ABI {abi:?}"),
+            Self::NoSuchProcess => write!(f, "no such process or tracee not stopped"),
+            Self::SuspendSeccompDenied(source) => write!(
+                f,
+                "PTRACE_O_SUSPEND_SECCOMP rejected, it needs CAP_SYS_ADMIN: {source}"
+            ),
+            Self::WatchdogExpired(status) => {
+                write!(f, "gave up waiting after too many stops (last {status:#x})")
+            }
+            Self::Thread { tid, source } => write!(f, "thread {tid}: {source}"),
+            #[cfg(feature = "object")]
+            Self::Elf(source) => write!(f, "failed to parse ELF: {source}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Ptrace { source, .. }
+            | Self::Wait(source)
+            | Self::Io(source)
+            | Self::SuspendSeccompDenied(source) => Some(source),
+            Self::Thread { source, .. } => Some(source.as_ref()),
+            #[cfg(feature = "object")]
+            Self::Elf(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        if err.raw_os_error() == Some(ESRCH) {
+            return Self::NoSuchProcess;
+        }
+
+        Self::Io(err)
+    }
+}
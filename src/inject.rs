@@ -0,0 +1,265 @@
+use std::{io, mem::MaybeUninit};
+
+use libc::{
+    c_int, c_uint, pid_t, sigset_t, user_regs_struct, SYS_mmap, SYS_munmap, MAP_ANONYMOUS,
+    MAP_PRIVATE, SIGTRAP, WIFSTOPPED, WSTOPSIG,
+};
+
+use crate::{
+    breakpoint::{clear_breakpoint, set_breakpoint},
+    error::{Error, Result},
+    mem::{read_memory, write_memory},
+    ptrace::{cont, get_sigmask, handle_signal, ptrace_event, set_sigmask, step_until},
+    regs::{abi, get_regs, restore_regs, save_regs, set_regs, Abi},
+};
+
+const RED_ZONE: u64 = 128;
+const SYSCALL_INSN: [u8; 2] = [0x0f, 0x05];
+/// `int 0x80`, the i386 counterpart of `syscall`, of the same length.
+const INT80_INSN: [u8; 2] = [0xcd, 0x80];
+// i386 syscall numbers, which libc only defines for i386 targets.
+const SYS_MMAP2_I386: u64 = 192;
+const SYS_MUNMAP_I386: u64 = 91;
+
+/// Calls the function at `func_addr` inside a stopped tracee with up to six
+/// integer arguments and returns its `rax`. The function returns into an
+/// `int3` temporarily patched over the current `rip`; that byte and the
+/// general-purpose and floating-point registers are restored afterwards,
+/// leaving the tracee where it was. Fails with the tracee's state restored if
+/// it stops for anything other than the return, and without restoring
+/// anything if it exits. An i386 tracee is refused with
+/// [`Error::UnsupportedAbi`].
+pub fn call_function(
+    pid: pid_t,
+    func_addr: u64,
+    args: &[u64],
+    attach: c_uint,
+    options: c_int,
+) -> Result<u64> {
+    if args.len() > 6 {
+        return Err(Error::TooManyArguments(args.len()));
+    }
+    if abi(pid)? == Abi::I386 {
+        return Err(Error::UnsupportedAbi(Abi::I386));
+    }
+
+    let saved = save_regs(pid)?;
+    let ret_addr = saved.regs.rip;
+    let original = set_breakpoint(pid, ret_addr)?;
+
+    let result = call(pid, &saved.regs, func_addr, args, ret_addr, attach, options);
+    if let Err(Error::UnexpectedStatus(status)) = result {
+        if !WIFSTOPPED(status) {
+            return result;
+        }
+    }
+
+    clear_breakpoint(pid, ret_addr, original)?;
+    restore_regs(pid, &saved)?;
+    result
+}
+
+fn call(
+    pid: pid_t,
+    saved: &user_regs_struct,
+    func_addr: u64,
+    args: &[u64],
+    ret_addr: u64,
+    attach: c_uint,
+    options: c_int,
+) -> Result<u64> {
+    let mut regs = *saved;
+    for (reg, &arg) in [
+        &mut regs.rdi,
+        &mut regs.rsi,
+        &mut regs.rdx,
+        &mut regs.rcx,
+        &mut regs.r8,
+        &mut regs.r9,
+    ]
+    .into_iter()
+    .zip(args)
+    {
+        *reg = arg;
+    }
+
+    // Skip the red zone and leave rsp 16-byte aligned before the return
+    // address is pushed, as it would be at a `call`.
+    regs.rsp = ((saved.rsp - RED_ZONE) & !0xf) - 8;
+    write_memory(pid, regs.rsp, &ret_addr.to_ne_bytes())?;
+    regs.rip = func_addr;
+    // No variadic vector arguments.
+    regs.rax = 0;
+    // Keep the kernel from restarting an interrupted syscall at the new rip.
+    regs.orig_rax = u64::MAX;
+    set_regs(pid, &regs)?;
+
+    cont(pid, 0)?;
+    let status = handle_signal(pid, attach, options)?;
+    if !WIFSTOPPED(status) || WSTOPSIG(status) != SIGTRAP {
+        return Err(Error::UnexpectedStatus(status));
+    }
+
+    let regs = get_regs(pid)?;
+    if regs.rip != ret_addr + 1 {
+        return Err(Error::UnexpectedStatus(status));
+    }
+
+    Ok(regs.rax)
+}
+
+/// Runs syscall `nr` in a stopped tracee by single-stepping a `syscall`
+/// instruction temporarily patched over the current `rip`, and returns the
+/// raw `rax`, a negated errno on failure. An i386 tracee gets `int 0x80`
+/// instead, with the arguments in `ebx`, `ecx`, `edx`, `esi`, `edi` and
+/// `ebp`; `nr` is always taken from the tracee's own syscall table. Signals are blocked for the step,
+/// so pending ones are delivered only once the tracee resumes. The signal
+/// mask, the registers and the patched bytes are restored afterwards, also
+/// when the step fails without the tracee exiting. This is what
+/// [`remote_mmap`] and [`remote_munmap`] are built on; any other syscall,
+/// such as `mprotect` or `openat`, can be made the same way.
+pub fn inject_syscall(
+    pid: pid_t,
+    nr: u64,
+    args: &[u64],
+    attach: c_uint,
+    options: c_int,
+) -> Result<u64> {
+    if args.len() > 6 {
+        return Err(Error::TooManyArguments(args.len()));
+    }
+
+    let abi = abi(pid)?;
+    let insn = match abi {
+        Abi::X86_64 => SYSCALL_INSN,
+        Abi::I386 => INT80_INSN,
+    };
+    let saved = get_regs(pid)?;
+    let mut original = [0; SYSCALL_INSN.len()];
+    read_memory(pid, saved.rip, &mut original)?;
+
+    // Keep pending signals from being delivered, and their handlers from
+    // running, while the `syscall` is patched in.
+    let mask = get_sigmask(pid)?;
+    let mut blocked = MaybeUninit::<sigset_t>::zeroed();
+    let blocked = unsafe {
+        libc::sigfillset(blocked.as_mut_ptr());
+        blocked.assume_init()
+    };
+    set_sigmask(pid, &blocked)?;
+
+    let result = write_memory(pid, saved.rip, &insn)
+        .and_then(|_| step_syscall(pid, abi, &saved, nr, args, attach, options));
+    if let Err(Error::UnexpectedStatus(status)) = result {
+        if !WIFSTOPPED(status) {
+            return result;
+        }
+    }
+
+    write_memory(pid, saved.rip, &original)?;
+    set_regs(pid, &saved)?;
+    set_sigmask(pid, &mask)?;
+    result
+}
+
+fn step_syscall(
+    pid: pid_t,
+    abi: Abi,
+    saved: &user_regs_struct,
+    nr: u64,
+    args: &[u64],
+    attach: c_uint,
+    options: c_int,
+) -> Result<u64> {
+    let mut regs = *saved;
+    // `get_regs` widens an i386 tracee's registers into the x86_64 fields of
+    // the same name, so `ebx` is set through `rbx` and so on.
+    let arg_regs = match abi {
+        Abi::X86_64 => [
+            &mut regs.rdi,
+            &mut regs.rsi,
+            &mut regs.rdx,
+            &mut regs.r10,
+            &mut regs.r8,
+            &mut regs.r9,
+        ],
+        Abi::I386 => [
+            &mut regs.rbx,
+            &mut regs.rcx,
+            &mut regs.rdx,
+            &mut regs.rsi,
+            &mut regs.rdi,
+            &mut regs.rbp,
+        ],
+    };
+    for (reg, &arg) in arg_regs.into_iter().zip(args) {
+        *reg = arg;
+    }
+    regs.rax = nr;
+    regs.orig_rax = u64::MAX;
+    set_regs(pid, &regs)?;
+
+    let status = step_until(pid, attach, options)?;
+    if !WIFSTOPPED(status) || WSTOPSIG(status) != SIGTRAP || ptrace_event(status) != 0 {
+        return Err(Error::UnexpectedStatus(status));
+    }
+
+    let regs = get_regs(pid)?;
+    if regs.rip != saved.rip + SYSCALL_INSN.len() as u64 {
+        return Err(Error::UnexpectedStatus(status));
+    }
+
+    Ok(regs.rax)
+}
+
+fn syscall_result(ret: u64) -> Result<u64> {
+    // The kernel returns errors as -4095..=-1.
+    if ret > -4096i64 as u64 {
+        return Err(Error::Io(io::Error::from_raw_os_error(
+            -(ret as i64) as i32,
+        )));
+    }
+
+    Ok(ret)
+}
+
+/// Maps `len` bytes of anonymous private memory with protection `prot` in the
+/// tracee and returns its address, using `mmap2` for an i386 tracee. A failed
+/// `mmap` is reported as [`Error::Io`] with the tracee's errno.
+pub fn remote_mmap(
+    pid: pid_t,
+    len: usize,
+    prot: c_int,
+    attach: c_uint,
+    options: c_int,
+) -> Result<u64> {
+    let args = [
+        0,
+        len as u64,
+        prot as u64,
+        (MAP_PRIVATE | MAP_ANONYMOUS) as u64,
+        -1i64 as u64,
+        0,
+    ];
+    let nr = match abi(pid)? {
+        Abi::X86_64 => SYS_mmap as u64,
+        Abi::I386 => SYS_MMAP2_I386,
+    };
+    syscall_result(inject_syscall(pid, nr, &args, attach, options)?)
+}
+
+pub fn remote_munmap(
+    pid: pid_t,
+    addr: u64,
+    len: usize,
+    attach: c_uint,
+    options: c_int,
+) -> Result<()> {
+    let args = [addr, len as u64];
+    let nr = match abi(pid)? {
+        Abi::X86_64 => SYS_munmap as u64,
+        Abi::I386 => SYS_MUNMAP_I386,
+    };
+    syscall_result(inject_syscall(pid, nr, &args, attach, options)?)?;
+    Ok(())
+}
@@ -1 +1,28 @@
+#[macro_use]
+mod macros;
+
+#[cfg(target_arch = "x86_64")]
+pub mod backtrace;
+#[cfg(target_arch = "x86_64")]
+pub mod breakpoint;
+#[cfg(target_arch = "x86_64")]
+pub mod coredump;
+#[cfg(target_arch = "x86_64")]
+pub mod debugreg;
+#[cfg(all(target_arch = "x86_64", feature = "iced-x86"))]
+pub mod disasm;
+pub mod error;
+#[cfg(target_arch = "x86_64")]
+pub mod inject;
+pub mod maps;
+pub mod mem;
+pub mod options;
+pub mod proc;
 pub mod ptrace;
+pub mod regs;
+pub mod siginfo;
+pub mod spawn;
+#[cfg(feature = "object")]
+pub mod symbols;
+pub mod syscall;
+pub mod tracee;
@@ -0,0 +1,21 @@
+/// `libc::ptrace` plus a `trace!` of the request, pid, addr and result. The
+/// message is only formatted when trace logging is enabled. Must be used in
+/// an `unsafe` block. A request that isn't a constant is passed after the
+/// name to log it under.
+macro_rules! ptrace {
+    ($request:ident, $pid:expr, $addr:expr, $data:expr) => {
+        ptrace!(stringify!($request), $request, $pid, $addr, $data)
+    };
+    ($name:expr, $request:expr, $pid:expr, $addr:expr, $data:expr) => {{
+        let pid = $pid;
+        let addr = $addr;
+        let ret = libc::ptrace($request, pid, addr, $data);
+        if log::log_enabled!(log::Level::Trace) {
+            // Logging must not clobber the errno callers check next.
+            let errno = *libc::__errno_location();
+            log::trace!("ptrace({}, {}, {:#x}) = {}", $name, pid, addr, ret);
+            *libc::__errno_location() = errno;
+        }
+        ret
+    }};
+}
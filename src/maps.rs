@@ -0,0 +1,173 @@
+use std::{fs, ops::BitOr, path::PathBuf};
+
+use libc::pid_t;
+
+use crate::error::{Error, Result};
+
+/// Permission bits of a mapping, as shown in the `rwxp` column.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Perms {
+    bits: u8,
+}
+
+impl Perms {
+    pub const READ: Self = Self { bits: 1 << 0 };
+    pub const WRITE: Self = Self { bits: 1 << 1 };
+    pub const EXEC: Self = Self { bits: 1 << 2 };
+    /// Set for `s` (shared) mappings, clear for `p` (private) ones.
+    pub const SHARED: Self = Self { bits: 1 << 3 };
+
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.bits & other.bits == other.bits
+    }
+
+    pub fn bits(self) -> u8 {
+        self.bits
+    }
+
+    fn parse(perms: &str) -> Option<Self> {
+        let perms = perms.as_bytes();
+        if perms.len() != 4 {
+            return None;
+        }
+
+        let mut parsed = Self::empty();
+        for (&c, (set, flag)) in perms.iter().zip([
+            (b'r', Self::READ),
+            (b'w', Self::WRITE),
+            (b'x', Self::EXEC),
+            (b's', Self::SHARED),
+        ]) {
+            if c == set {
+                parsed = parsed | flag;
+            } else if c != b'-' && c != b'p' {
+                return None;
+            }
+        }
+
+        Some(parsed)
+    }
+}
+
+impl BitOr for Perms {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self {
+            bits: self.bits | rhs.bits,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub start: u64,
+    pub end: u64,
+    pub perms: Perms,
+    pub offset: u64,
+    /// Device `(major, minor)` of the backing file.
+    pub dev: (u32, u32),
+    pub inode: u64,
+    /// Backing file, or a pseudo-path such as `[heap]` or `[stack]`; `None`
+    /// for anonymous mappings.
+    pub path: Option<PathBuf>,
+}
+
+impl MemoryRegion {
+    pub fn contains(&self, addr: u64) -> bool {
+        self.start <= addr && addr < self.end
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(6, ' ');
+        let (start, end) = fields.next()?.split_once('-')?;
+        let perms = Perms::parse(fields.next()?)?;
+        let offset = fields.next()?;
+        let (major, minor) = fields.next()?.split_once(':')?;
+        let inode = fields.next()?;
+        // The path is padded with spaces and may itself contain spaces.
+        let path = fields
+            .next()
+            .map(str::trim_start)
+            .filter(|path| !path.is_empty());
+
+        Some(Self {
+            start: u64::from_str_radix(start, 16).ok()?,
+            end: u64::from_str_radix(end, 16).ok()?,
+            perms,
+            offset: u64::from_str_radix(offset, 16).ok()?,
+            dev: (
+                u32::from_str_radix(major, 16).ok()?,
+                u32::from_str_radix(minor, 16).ok()?,
+            ),
+            inode: inode.parse().ok()?,
+            path: path.map(PathBuf::from),
+        })
+    }
+}
+
+pub fn read_maps(pid: pid_t) -> Result<Vec<MemoryRegion>> {
+    let maps = fs::read_to_string(format!("/proc/{pid}/maps"))?;
+    maps.lines()
+        .map(|line| MemoryRegion::parse(line).ok_or_else(|| Error::Malformed(line.to_owned())))
+        .collect()
+}
+
+/// Returns the lowest start address of the regions whose path ends with
+/// `name`, e.g. `libc.so.6`, or `None` if no such module is mapped. For a PIE
+/// executable this is the base its file-relative addresses are offset from.
+/// That base is the binary's first mapping, where the ELF header is loaded,
+/// not its first executable one: linkers that split out the text segment map
+/// it at a page offset above the base.
+pub fn module_base(pid: pid_t, name: &str) -> Result<Option<u64>> {
+    let base = read_maps(pid)?
+        .into_iter()
+        .filter(|region| is_module(region, name))
+        .map(|region| region.start)
+        .min();
+
+    Ok(base)
+}
+
+fn is_module(region: &MemoryRegion, name: &str) -> bool {
+    region.path.as_ref().is_some_and(|path| {
+        path.as_os_str()
+            .as_encoded_bytes()
+            .ends_with(name.as_bytes())
+    })
+}
+
+/// Translates `offset` into the file of the module `name`, matched as in
+/// [`module_base`], to the address it is mapped at, looking through each of
+/// the module's mappings since text and data are mapped from different
+/// offsets. Returns `None` if that part of the file isn't mapped. A page at a
+/// segment boundary can be mapped twice, at the end of one mapping and the
+/// start of the next; its offsets are attributed to the later mapping.
+pub fn file_offset_to_addr(pid: pid_t, name: &str, offset: u64) -> Result<Option<u64>> {
+    let addr = read_maps(pid)?
+        .into_iter()
+        .filter(|region| is_module(region, name))
+        .filter(|region| {
+            offset >= region.offset && offset - region.offset < region.end - region.start
+        })
+        .max_by_key(|region| region.offset)
+        .map(|region| region.start + (offset - region.offset));
+
+    Ok(addr)
+}
+
+/// Translates `addr` to an offset into the file mapped there. Returns `None`
+/// if `addr` isn't in a file-backed mapping.
+pub fn addr_to_file_offset(pid: pid_t, addr: u64) -> Result<Option<u64>> {
+    let offset = read_maps(pid)?
+        .into_iter()
+        .find(|region| region.contains(addr))
+        .filter(|region| region.path.as_ref().is_some_and(|path| path.is_absolute()))
+        .map(|region| region.offset + (addr - region.start));
+
+    Ok(offset)
+}
@@ -0,0 +1,290 @@
+use std::{
+    fs::File,
+    io,
+    mem::{size_of, MaybeUninit},
+    os::fd::AsRawFd,
+    slice,
+};
+
+use libc::{c_long, c_void, iovec, off_t, pid_t, EIO, PTRACE_PEEKDATA, PTRACE_POKEDATA};
+
+use crate::{
+    error::{Error, Result},
+    maps::{read_maps, Perms},
+    ptrace::peek,
+};
+
+const WORD_SIZE: usize = size_of::<c_long>();
+const SCAN_CHUNK_SIZE: usize = 1 << 20;
+
+fn peek_data(pid: pid_t, addr: u64) -> Result<c_long> {
+    peek(PTRACE_PEEKDATA, "PTRACE_PEEKDATA", pid, addr)
+}
+
+fn poke_data(pid: pid_t, addr: u64, word: c_long) -> Result<()> {
+    let err = unsafe { ptrace!(PTRACE_POKEDATA, pid, addr, word) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_POKEDATA"));
+    }
+
+    Ok(())
+}
+
+/// Returns the number of bytes read, which is less than `buf.len()` when the
+/// read runs into an unmapped page after the first aligned word.
+pub fn read_memory(pid: pid_t, addr: u64, buf: &mut [u8]) -> Result<usize> {
+    let mut read = 0;
+
+    // Peek aligned words so that bytes just before an unmapped page can be
+    // read without the word running into it.
+    while read < buf.len() {
+        let cur = addr.wrapping_add(read as u64);
+        let offset = (cur % WORD_SIZE as u64) as usize;
+        let len = (WORD_SIZE - offset).min(buf.len() - read);
+        let word = match peek_data(pid, cur - offset as u64) {
+            Ok(word) => word,
+            Err(_) if read != 0 => break,
+            Err(err) => return Err(err),
+        };
+
+        buf[read..read + len].copy_from_slice(&word.to_ne_bytes()[offset..offset + len]);
+        read += len;
+    }
+
+    Ok(read)
+}
+
+/// The tracee must be stopped. Partial words at either end are read back and
+/// merged so neighbouring bytes are preserved. Returns the number of bytes
+/// written, which is less than `buf.len()` when the write runs into an
+/// unmapped page after the first word.
+pub fn write_memory(pid: pid_t, addr: u64, buf: &[u8]) -> Result<usize> {
+    let mut written = 0;
+
+    while written < buf.len() {
+        let cur = addr.wrapping_add(written as u64);
+        let offset = (cur % WORD_SIZE as u64) as usize;
+        let aligned = cur - offset as u64;
+        let len = (WORD_SIZE - offset).min(buf.len() - written);
+        let src = &buf[written..written + len];
+
+        let result = if len == WORD_SIZE {
+            poke_data(pid, aligned, c_long::from_ne_bytes(src.try_into().unwrap()))
+        } else {
+            peek_data(pid, aligned).and_then(|word| {
+                let mut bytes = word.to_ne_bytes();
+                bytes[offset..offset + len].copy_from_slice(src);
+                poke_data(pid, aligned, c_long::from_ne_bytes(bytes))
+            })
+        };
+
+        match result {
+            Ok(()) => written += len,
+            Err(_) if written != 0 => break,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(written)
+}
+
+/// Reads `buf.len()` bytes with a single `process_vm_readv` call. Returns the
+/// number of bytes transferred, which is short when the remote range runs
+/// into an unmapped page.
+pub fn read_memory_vm(pid: pid_t, addr: u64, buf: &mut [u8]) -> Result<usize> {
+    let local = iovec {
+        iov_base: buf.as_mut_ptr().cast::<c_void>(),
+        iov_len: buf.len(),
+    };
+    let remote = iovec {
+        iov_base: addr as *mut c_void,
+        iov_len: buf.len(),
+    };
+
+    let read = unsafe { libc::process_vm_readv(pid, &local, 1, &remote, 1, 0) };
+    if read == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(read as usize)
+}
+
+/// Writes `buf` with a single `process_vm_writev` call. Unlike
+/// `PTRACE_POKEDATA` this honours page protections, so writes into read-only
+/// mappings such as code pages fail; use [`write_memory`] to patch those.
+/// Returns the number of bytes transferred, which is short when the remote
+/// range runs into unmapped or unwritable memory.
+pub fn write_memory_vm(pid: pid_t, addr: u64, buf: &[u8]) -> Result<usize> {
+    let local = iovec {
+        iov_base: buf.as_ptr() as *mut c_void,
+        iov_len: buf.len(),
+    };
+    let remote = iovec {
+        iov_base: addr as *mut c_void,
+        iov_len: buf.len(),
+    };
+
+    let written = unsafe { libc::process_vm_writev(pid, &local, 1, &remote, 1, 0) };
+    if written == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(written as usize)
+}
+
+/// A cached `/proc/<pid>/mem` handle. The tracee must be ptrace-stopped for
+/// the kernel to permit reads through it.
+pub struct ProcMem {
+    file: File,
+}
+
+impl ProcMem {
+    pub fn open(pid: pid_t) -> Result<Self> {
+        let file = File::open(format!("/proc/{pid}/mem"))?;
+        Ok(Self { file })
+    }
+
+    pub fn read(&self, addr: u64, buf: &mut [u8]) -> Result<usize> {
+        let fd = self.file.as_raw_fd();
+        let read = unsafe {
+            libc::pread(
+                fd,
+                buf.as_mut_ptr().cast::<c_void>(),
+                buf.len(),
+                addr as off_t,
+            )
+        };
+        if read == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Ok(read as usize)
+    }
+}
+
+/// Fallback for when `process_vm_readv` is unavailable. Opens
+/// `/proc/<pid>/mem` for a single read; keep a [`ProcMem`] around instead
+/// when reading the same tracee repeatedly.
+pub fn read_memory_procmem(pid: pid_t, addr: u64, buf: &mut [u8]) -> Result<usize> {
+    ProcMem::open(pid)?.read(addr, buf)
+}
+
+/// Reads the NUL-terminated string at `addr`, without the terminator. The
+/// string is returned truncated if no NUL is found within `max_len` bytes or
+/// before an unmapped page; an unreadable `addr` is an error.
+pub fn read_cstring(pid: pid_t, addr: u64, max_len: usize) -> Result<Vec<u8>> {
+    let mut string = Vec::new();
+    let mut cur = addr;
+
+    while string.len() < max_len {
+        let offset = (cur % WORD_SIZE as u64) as usize;
+        let word = match peek_data(pid, cur - offset as u64) {
+            Ok(word) => word,
+            Err(_) if cur != addr => break,
+            Err(err) => return Err(err),
+        };
+
+        for &byte in &word.to_ne_bytes()[offset..] {
+            if byte == 0 || string.len() == max_len {
+                return Ok(string);
+            }
+
+            string.push(byte);
+        }
+
+        cur = cur.wrapping_add((WORD_SIZE - offset) as u64);
+    }
+
+    Ok(string)
+}
+
+/// Returns the address of every match of `pattern`, where `None` matches any
+/// byte, in the tracee's readable mappings. Matches never span two regions.
+/// Regions that can't be read, such as `[vvar]`, are skipped, and the scan of
+/// a region stops at the first page that can't be read.
+pub fn scan(pid: pid_t, pattern: &[Option<u8>]) -> Result<Vec<u64>> {
+    let mut matches = Vec::new();
+    if pattern.is_empty() {
+        return Ok(matches);
+    }
+
+    let overlap = pattern.len() - 1;
+    let mut buf = vec![0; SCAN_CHUNK_SIZE + overlap];
+    let regions = read_maps(pid)?;
+
+    for region in regions
+        .iter()
+        .filter(|region| region.perms.contains(Perms::READ))
+    {
+        let mut addr = region.start;
+        while addr < region.end {
+            // Read past the chunk so that matches crossing into the next one
+            // are found here.
+            let len = ((region.end - addr) as usize).min(buf.len());
+            let Ok(read) = read_memory_vm(pid, addr, &mut buf[..len]) else {
+                break;
+            };
+
+            let found = buf[..read]
+                .windows(pattern.len())
+                .take(SCAN_CHUNK_SIZE)
+                .enumerate()
+                .filter(|(_, window)| {
+                    window
+                        .iter()
+                        .zip(pattern)
+                        .all(|(byte, expected)| expected.is_none_or(|expected| *byte == expected))
+                })
+                .map(|(offset, _)| addr + offset as u64);
+            matches.extend(found);
+
+            if read < len {
+                break;
+            }
+            addr += SCAN_CHUNK_SIZE as u64;
+        }
+    }
+
+    Ok(matches)
+}
+
+/// Types that can be read from and written to tracee memory as raw bytes.
+///
+/// # Safety
+///
+/// Implementors must have no padding, no references or pointers to tracer
+/// memory, and be valid for any bit pattern.
+pub unsafe trait Pod: Copy {}
+
+macro_rules! impl_pod {
+    ($($ty:ty),*) => {
+        $(unsafe impl Pod for $ty {})*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// Reads a `T` from `addr`. Fails with `EIO` if the value straddles an
+/// unmapped page.
+pub fn read_val<T: Pod>(pid: pid_t, addr: u64) -> Result<T> {
+    let mut val = MaybeUninit::<T>::zeroed();
+    let buf = unsafe { slice::from_raw_parts_mut(val.as_mut_ptr().cast::<u8>(), size_of::<T>()) };
+    if read_memory(pid, addr, buf)? < buf.len() {
+        return Err(Error::Io(io::Error::from_raw_os_error(EIO)));
+    }
+
+    Ok(unsafe { val.assume_init() })
+}
+
+/// Writes `val` to `addr`. Fails with `EIO` if the value straddles an
+/// unmapped page, in which case the part before it has been written.
+pub fn write_val<T: Pod>(pid: pid_t, addr: u64, val: T) -> Result<()> {
+    let buf = unsafe { slice::from_raw_parts((&val as *const T).cast::<u8>(), size_of::<T>()) };
+    if write_memory(pid, addr, buf)? < buf.len() {
+        return Err(Error::Io(io::Error::from_raw_os_error(EIO)));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use std::ops::BitOr;
+
+use libc::{
+    c_int, PTRACE_EVENT_CLONE, PTRACE_EVENT_EXEC, PTRACE_EVENT_EXIT, PTRACE_EVENT_FORK,
+    PTRACE_EVENT_SECCOMP, PTRACE_EVENT_VFORK, PTRACE_EVENT_VFORK_DONE, PTRACE_O_EXITKILL,
+    PTRACE_O_SUSPEND_SECCOMP, PTRACE_O_TRACECLONE, PTRACE_O_TRACEEXEC, PTRACE_O_TRACEEXIT,
+    PTRACE_O_TRACEFORK, PTRACE_O_TRACESECCOMP, PTRACE_O_TRACESYSGOOD, PTRACE_O_TRACEVFORK,
+    PTRACE_O_TRACEVFORKDONE,
+};
+
+/// Builder for the `PTRACE_O_*` flags passed to `PTRACE_SEIZE`,
+/// `PTRACE_SETOPTIONS` and `handle_signal`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Options {
+    bits: c_int,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn flag(mut self, flag: c_int, enable: bool) -> Self {
+        if enable {
+            self.bits |= flag;
+        } else {
+            self.bits &= !flag;
+        }
+
+        self
+    }
+
+    pub fn trace_fork(self, enable: bool) -> Self {
+        self.flag(PTRACE_O_TRACEFORK, enable)
+    }
+
+    pub fn trace_vfork(self, enable: bool) -> Self {
+        self.flag(PTRACE_O_TRACEVFORK, enable)
+    }
+
+    pub fn trace_clone(self, enable: bool) -> Self {
+        self.flag(PTRACE_O_TRACECLONE, enable)
+    }
+
+    pub fn trace_exec(self, enable: bool) -> Self {
+        self.flag(PTRACE_O_TRACEEXEC, enable)
+    }
+
+    pub fn trace_exit(self, enable: bool) -> Self {
+        self.flag(PTRACE_O_TRACEEXIT, enable)
+    }
+
+    pub fn trace_seccomp(self, enable: bool) -> Self {
+        self.flag(PTRACE_O_TRACESECCOMP, enable)
+    }
+
+    /// Marks syscall-stops with `SIGTRAP | 0x80` so they can be told apart
+    /// from other `SIGTRAP`s without a `PTRACE_GETSIGINFO` round trip.
+    pub fn trace_sysgood(self, enable: bool) -> Self {
+        self.flag(PTRACE_O_TRACESYSGOOD, enable)
+    }
+
+    pub fn trace_vfork_done(self, enable: bool) -> Self {
+        self.flag(PTRACE_O_TRACEVFORKDONE, enable)
+    }
+
+    /// Has the kernel `SIGKILL` the tracee when the tracer exits, instead of
+    /// leaving it behind detached mid-trace. Like all options it only takes
+    /// effect once set, so pass it to `seize` to cover the whole session.
+    pub fn exit_kill(self, enable: bool) -> Self {
+        self.flag(PTRACE_O_EXITKILL, enable)
+    }
+
+    /// Suspends the tracee's seccomp filters while it is traced, so injected
+    /// syscalls aren't killed by its own sandbox. Requires `CAP_SYS_ADMIN` in
+    /// the initial user namespace, a tracer not itself under seccomp, and a
+    /// kernel built with `CONFIG_CHECKPOINT_RESTORE`. Prefer setting it with
+    /// `set_options`, which reports a rejection as
+    /// [`Error::SuspendSeccompDenied`](crate::error::Error::SuspendSeccompDenied);
+    /// `seize` fails with an `EPERM` that can't be told apart from being
+    /// denied the attach itself.
+    pub fn suspend_seccomp(self, enable: bool) -> Self {
+        self.flag(PTRACE_O_SUSPEND_SECCOMP, enable)
+    }
+
+    pub fn bits(&self) -> c_int {
+        self.bits
+    }
+}
+
+/// Which ptrace events `handle_signal_masked` reports. Events that the
+/// options have the kernel deliver but the mask leaves out are resumed past.
+/// `PTRACE_EVENT_STOP` isn't covered: it is reported whenever delivered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct EventMask {
+    bits: u8,
+}
+
+impl EventMask {
+    pub const FORK: Self = Self { bits: 1 << 0 };
+    pub const VFORK: Self = Self { bits: 1 << 1 };
+    pub const CLONE: Self = Self { bits: 1 << 2 };
+    pub const VFORK_DONE: Self = Self { bits: 1 << 3 };
+    pub const EXEC: Self = Self { bits: 1 << 4 };
+    pub const EXIT: Self = Self { bits: 1 << 5 };
+    pub const SECCOMP: Self = Self { bits: 1 << 6 };
+
+    const EVENTS: [(Self, c_int, c_int); 7] = [
+        (Self::FORK, PTRACE_EVENT_FORK, PTRACE_O_TRACEFORK),
+        (Self::VFORK, PTRACE_EVENT_VFORK, PTRACE_O_TRACEVFORK),
+        (Self::CLONE, PTRACE_EVENT_CLONE, PTRACE_O_TRACECLONE),
+        (
+            Self::VFORK_DONE,
+            PTRACE_EVENT_VFORK_DONE,
+            PTRACE_O_TRACEVFORKDONE,
+        ),
+        (Self::EXEC, PTRACE_EVENT_EXEC, PTRACE_O_TRACEEXEC),
+        (Self::EXIT, PTRACE_EVENT_EXIT, PTRACE_O_TRACEEXIT),
+        (Self::SECCOMP, PTRACE_EVENT_SECCOMP, PTRACE_O_TRACESECCOMP),
+    ];
+
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Every event the `PTRACE_O_*` bits in `options` enable, which is what
+    /// `handle_signal` reports.
+    pub fn from_options(options: c_int) -> Self {
+        Self::EVENTS
+            .into_iter()
+            .filter(|&(_, _, option)| options & option != 0)
+            .fold(Self::empty(), |mask, (flag, _, _)| mask | flag)
+    }
+
+    /// The flag for a `PTRACE_EVENT_*` value, if it is one the mask covers.
+    pub fn for_event(event: c_int) -> Option<Self> {
+        Self::EVENTS
+            .into_iter()
+            .find(|&(_, value, _)| value == event)
+            .map(|(flag, _, _)| flag)
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.bits & other.bits == other.bits
+    }
+
+    pub fn bits(self) -> u8 {
+        self.bits
+    }
+}
+
+impl BitOr for EventMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self {
+            bits: self.bits | rhs.bits,
+        }
+    }
+}
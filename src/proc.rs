@@ -0,0 +1,154 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    mem::size_of,
+    os::fd::{FromRawFd, OwnedFd},
+    path::PathBuf,
+};
+
+use libc::{c_int, pid_t, SYS_pidfd_open, AT_NULL};
+
+use crate::error::{Error, Result};
+
+/// Lists the tids of every thread in the thread group of `pid`, the leader
+/// included, in no particular order. Threads can come and go while this
+/// runs, so attaching to all of them means repeating until no new tid shows
+/// up.
+pub fn list_tasks(pid: pid_t) -> Result<Vec<pid_t>> {
+    fs::read_dir(format!("/proc/{pid}/task"))?
+        .map(|entry| {
+            let name = entry?.file_name();
+            let name = name.to_string_lossy();
+            name.parse()
+                .map_err(|_| Error::Malformed(name.into_owned()))
+        })
+        .collect()
+}
+
+/// Opens a pidfd for `pid` (Linux 5.3+), which keeps referring to the same
+/// process even once the pid is reused. It polls readable when the process
+/// exits, but not on ptrace stops, which still have to be waited for.
+pub fn pidfd_open(pid: pid_t) -> Result<OwnedFd> {
+    let fd = unsafe { libc::syscall(SYS_pidfd_open, pid, 0) };
+    if fd == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+}
+
+/// Returns the thread-group id of `pid`, which is the pid of its group
+/// leader.
+pub fn tgid(pid: pid_t) -> Result<pid_t> {
+    let status = fs::read_to_string(format!("/proc/{pid}/status"))?;
+    let line = status
+        .lines()
+        .find(|line| line.starts_with("Tgid:"))
+        .ok_or_else(|| Error::Malformed(status.clone()))?;
+
+    line["Tgid:".len()..]
+        .trim()
+        .parse()
+        .map_err(|_| Error::Malformed(line.to_string()))
+}
+
+/// Returns the name of the thread `tid`, as set by `prctl(PR_SET_NAME)` and
+/// truncated by the kernel to 15 bytes. It may be any bytes, so it is lossily
+/// converted like [`cmdline`].
+pub fn thread_name(tid: pid_t) -> Result<String> {
+    let comm = fs::read(format!("/proc/{tid}/comm"))?;
+    let comm = comm.strip_suffix(b"\n").unwrap_or(&comm);
+    Ok(String::from_utf8_lossy(comm).into_owned())
+}
+
+/// Returns the state letter of the thread `tid`, e.g. `R` (running), `S`
+/// (sleeping), `D` (uninterruptible), `T` (stopped) or `t` (ptrace-stopped).
+pub fn thread_state(tid: pid_t) -> Result<char> {
+    // Read as bytes, since the name in it needn't be UTF-8.
+    let stat = fs::read(format!("/proc/{tid}/stat"))?;
+    // The name is in parentheses and may itself contain them.
+    stat.iter()
+        .rposition(|&byte| byte == b')')
+        .and_then(|end| stat[end + 1..].iter().find(|&&byte| byte != b' '))
+        .map(|&state| char::from(state))
+        .ok_or_else(|| Error::Malformed(String::from_utf8_lossy(&stat).into_owned()))
+}
+
+/// Returns what the tracee's `fd` refers to: a path for files, or a
+/// pseudo-path such as `socket:[1234]`, `pipe:[5678]` or `anon_inode:[eventfd]`.
+/// Returns `None` if `fd` isn't open.
+pub fn fd_path(pid: pid_t, fd: c_int) -> Result<Option<PathBuf>> {
+    match fs::read_link(format!("/proc/{pid}/fd/{fd}")) {
+        Ok(path) => Ok(Some(path)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Lists the tracee's open fds in ascending order, with [`fd_path`]'s target
+/// for each. Fds closed while the list is being read are left out.
+pub fn list_fds(pid: pid_t) -> Result<Vec<(c_int, PathBuf)>> {
+    let mut fds = Vec::new();
+    for entry in fs::read_dir(format!("/proc/{pid}/fd"))? {
+        let name = entry?.file_name();
+        let name = name.to_string_lossy();
+        let fd = name
+            .parse()
+            .map_err(|_| Error::Malformed(name.into_owned()))?;
+        if let Some(path) = fd_path(pid, fd)? {
+            fds.push((fd, path));
+        }
+    }
+
+    fds.sort_unstable_by_key(|&(fd, _)| fd);
+    Ok(fds)
+}
+
+fn read_nul_separated(path: String) -> Result<Vec<String>> {
+    let data = fs::read(path)?;
+    // Processes that rewrite their argv may drop the final NUL.
+    let data = data.strip_suffix(&[0]).unwrap_or(&data);
+    if data.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let strings = data
+        .split(|&byte| byte == 0)
+        .map(|string| String::from_utf8_lossy(string).into_owned())
+        .collect();
+    Ok(strings)
+}
+
+/// Returns the tracee's arguments, `argv[0]` included. Bytes that aren't
+/// valid UTF-8 are replaced with U+FFFD. Empty for zombies and kernel
+/// threads.
+pub fn cmdline(pid: pid_t) -> Result<Vec<String>> {
+    read_nul_separated(format!("/proc/{pid}/cmdline"))
+}
+
+/// Returns the tracee's initial environment as `KEY=value` strings, lossily
+/// converted like [`cmdline`]. Reading it needs the same access as ptrace, so
+/// it fails for processes of other users.
+pub fn environ(pid: pid_t) -> Result<Vec<String>> {
+    read_nul_separated(format!("/proc/{pid}/environ"))
+}
+
+/// Reads the auxiliary vector the kernel passed to the tracee, keyed by
+/// `AT_*` type. Useful entries include `AT_ENTRY`, where the program starts
+/// once the dynamic loader is done, `AT_PHDR` and `AT_RANDOM`.
+pub fn read_auxv(pid: pid_t) -> Result<HashMap<u64, u64>> {
+    let auxv = fs::read(format!("/proc/{pid}/auxv"))?;
+    let pairs = auxv
+        .chunks_exact(2 * size_of::<u64>())
+        .map(|pair| {
+            let (key, value) = pair.split_at(size_of::<u64>());
+            (
+                u64::from_ne_bytes(key.try_into().unwrap()),
+                u64::from_ne_bytes(value.try_into().unwrap()),
+            )
+        })
+        .take_while(|&(key, _)| key != AT_NULL)
+        .collect();
+
+    Ok(pairs)
+}
@@ -1,86 +1,399 @@
 use std::{
-    io::{Error, Result},
-    mem::MaybeUninit,
+    fs, io,
+    mem::{size_of, MaybeUninit},
+    ops::ControlFlow,
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
 };
 
 use libc::{
-    c_int, c_uint, pid_t, siginfo_t, EINVAL, PTRACE_CONT, PTRACE_EVENT_CLONE, PTRACE_EVENT_EXEC,
-    PTRACE_EVENT_EXIT, PTRACE_EVENT_FORK, PTRACE_EVENT_SECCOMP, PTRACE_EVENT_STOP,
-    PTRACE_EVENT_VFORK, PTRACE_EVENT_VFORK_DONE, PTRACE_GETSIGINFO, PTRACE_O_TRACECLONE,
-    PTRACE_O_TRACEEXEC, PTRACE_O_TRACEEXIT, PTRACE_O_TRACEFORK, PTRACE_O_TRACESECCOMP,
-    PTRACE_O_TRACESYSGOOD, PTRACE_O_TRACEVFORKDONE, PTRACE_SEIZE, SIGSTOP, SIGTRAP, SIGTSTP,
-    SIGTTIN, SIGTTOU, WIFEXITED, WIFSIGNALED, WIFSTOPPED, WSTOPSIG, __WALL,
+    c_int, c_long, c_uint, c_ulong, pid_t, siginfo_t, sigset_t, ECHILD, EINVAL, PTRACE_ATTACH,
+    PTRACE_CONT, PTRACE_DETACH, PTRACE_EVENT_CLONE, PTRACE_EVENT_EXEC, PTRACE_EVENT_EXIT,
+    PTRACE_EVENT_FORK, PTRACE_EVENT_SECCOMP, PTRACE_EVENT_STOP, PTRACE_EVENT_VFORK,
+    PTRACE_EVENT_VFORK_DONE, PTRACE_GETEVENTMSG, PTRACE_GETSIGINFO, PTRACE_GETSIGMASK,
+    PTRACE_INTERRUPT, PTRACE_LISTEN, PTRACE_O_TRACESYSGOOD, PTRACE_PEEKUSER, PTRACE_POKEUSER,
+    PTRACE_SEIZE, PTRACE_SETOPTIONS, PTRACE_SETSIGMASK, PTRACE_SINGLESTEP, PTRACE_SYSCALL,
+    SECCOMP_RET_DATA, SIGBUS, SIGSEGV, SIGSTOP, SIGTRAP, SIGTSTP, SIGTTIN, SIGTTOU, TRAP_BRKPT,
+    TRAP_TRACE, WCOREDUMP, WEXITSTATUS, WIFEXITED, WIFSIGNALED, WIFSTOPPED, WNOHANG, WSTOPSIG,
+    WTERMSIG, __WALL,
 };
-use log::warn;
+use log::{debug, warn};
+#[cfg(feature = "tokio")]
+use tokio::signal::unix::{signal, SignalKind};
 
-pub fn handle_signal(pid: pid_t, attach: c_uint, options: c_int) -> Result<c_int> {
-    fn waitpid(pid: pid_t) -> Result<c_int> {
-        let mut status = 0;
-        let err = unsafe { libc::waitpid(pid, &mut status, __WALL) };
-        if err == -1 {
-            return Err(Error::last_os_error());
+use crate::{
+    error::{Error, Result},
+    options::EventMask,
+    proc::list_tasks,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+const SYSCALL_SIGTRAP: c_int = SIGTRAP | 0x80;
+// The status the kernel reports for a syscall-stop under PTRACE_O_TRACESYSGOOD.
+const SYSCALL_STATUS: c_int = (SYSCALL_SIGTRAP << 8) | 0x7f;
+
+pub(crate) fn waitpid(pid: pid_t) -> Result<c_int> {
+    wait_with_flags(pid, __WALL)
+}
+
+/// Blocking `waitpid` with caller-chosen flags, for when the `__WALL` used
+/// everywhere else would reap children a surrounding supervisor expects to
+/// see: `0` waits for non-clone children only, `__WCLONE` for clone children
+/// only. Retries on `EINTR` and returns the raw status.
+pub fn wait_with_flags(pid: pid_t, flags: c_int) -> Result<c_int> {
+    let mut status = 0;
+    loop {
+        let err = unsafe { libc::waitpid(pid, &mut status, flags) };
+        if err != -1 {
+            return Ok(status);
         }
 
-        Ok(status)
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(Error::Wait(err));
+        }
     }
+}
 
-    fn get_siginfo(pid: pid_t) -> Result<siginfo_t> {
-        let mut siginfo = unsafe { MaybeUninit::<siginfo_t>::zeroed().assume_init() };
-        let err = unsafe { libc::ptrace(PTRACE_GETSIGINFO, pid, 0, &mut siginfo) };
-        if err == -1 {
-            return Err(Error::last_os_error());
+/// Non-blocking `waitpid`: returns `None` if no status is pending and the
+/// raw status otherwise, undecoded and without resuming the tracee.
+pub fn try_wait(pid: pid_t) -> Result<Option<c_int>> {
+    let mut status = 0;
+    loop {
+        match unsafe { libc::waitpid(pid, &mut status, __WALL | WNOHANG) } {
+            0 => return Ok(None),
+            -1 => {}
+            _ => return Ok(Some(status)),
         }
 
-        Ok(siginfo)
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(Error::Wait(err));
+        }
     }
+}
+
+/// Blocking `waitpid(-1, __WALL)`, for waiting on several tracees at once,
+/// forked children from other thread groups included. Returns the pid that
+/// reported with its raw status, undecoded and without resuming it. Children
+/// that aren't traced are reaped too, so this suits tracers that own all of
+/// theirs. Fails with `ECHILD` in [`Error::Wait`] once none are left.
+pub fn wait_any() -> Result<(pid_t, c_int)> {
+    loop {
+        if let Some(reported) = wait_any_with_flags(__WALL)? {
+            return Ok(reported);
+        }
+    }
+}
 
-    fn cont(pid: pid_t, sig: c_int) -> Result<()> {
-        let err = unsafe { libc::ptrace(PTRACE_CONT, pid, 0, sig) };
-        if err == -1 {
-            return Err(Error::last_os_error());
+/// Non-blocking [`wait_any`]: returns `None` if no child has a status
+/// pending.
+pub fn try_wait_any() -> Result<Option<(pid_t, c_int)>> {
+    wait_any_with_flags(__WALL | WNOHANG)
+}
+
+fn wait_any_with_flags(flags: c_int) -> Result<Option<(pid_t, c_int)>> {
+    let mut status = 0;
+    loop {
+        match unsafe { libc::waitpid(-1, &mut status, flags) } {
+            0 => return Ok(None),
+            -1 => {}
+            pid => return Ok(Some((pid, status))),
         }
 
-        Ok(())
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(Error::Wait(err));
+        }
     }
+}
 
-    let status = waitpid(pid)?;
+/// Collects every pending `(tid, status)` of the threads of `group` without
+/// blocking, so a burst of simultaneous thread stops can be handled before
+/// any thread is resumed. Threads are found through `/proc/<group>/task`
+/// rather than with `waitpid(-1)`, which would also reap unrelated children;
+/// threads that aren't traced are skipped.
+pub fn drain_statuses(group: pid_t) -> Result<Vec<(pid_t, c_int)>> {
+    let mut statuses = Vec::new();
+    loop {
+        let drained = statuses.len();
+        let tids = match list_tasks(group) {
+            Ok(tids) => tids,
+            // The whole group may have just been reaped.
+            Err(_) if !statuses.is_empty() => return Ok(statuses),
+            Err(err) => return Err(err),
+        };
 
-    if WIFSTOPPED(status) {
-        let signal = WSTOPSIG(status);
+        for tid in tids {
+            match try_wait(tid) {
+                Ok(Some(status)) => statuses.push((tid, status)),
+                Ok(None) => {}
+                Err(err) if err.raw_os_error() == Some(ECHILD) => {}
+                Err(err) => return Err(err),
+            }
+        }
 
-        const SYSCALL_SIGTRAP: c_int = SIGTRAP | 0x80;
-        match signal {
-            SIGTRAP => match signal >> 16 {
-                PTRACE_EVENT_VFORK if options & PTRACE_O_TRACEFORK != 0 => {
-                    return Ok(status);
-                }
+        if statuses.len() == drained {
+            return Ok(statuses);
+        }
+    }
+}
 
-                PTRACE_EVENT_FORK if options & PTRACE_O_TRACEFORK != 0 => {
-                    return Ok(status);
-                }
+pub fn handle_signal(pid: pid_t, attach: c_uint, options: c_int) -> Result<c_int> {
+    handle_signal_filtered(pid, attach, options, |signal| signal)
+}
 
-                PTRACE_EVENT_CLONE if options & PTRACE_O_TRACECLONE != 0 => {
-                    return Ok(status);
-                }
+/// Resumes the tracee with `cont` and waits for its next stop with
+/// `handle_signal`.
+pub fn resume_until(pid: pid_t, attach: c_uint, options: c_int) -> Result<c_int> {
+    cont(pid, 0)?;
+    handle_signal(pid, attach, options)
+}
 
-                PTRACE_EVENT_VFORK_DONE if options & PTRACE_O_TRACEVFORKDONE != 0 => {
-                    return Ok(status);
-                }
+/// Single-steps the tracee and waits for the stop, normally the `SIGTRAP`
+/// after one instruction. A signal that arrives first is delivered by
+/// stepping again with it rather than by `PTRACE_CONT`, so the tracee never
+/// runs freely; if it has a handler, the step stops on the handler's first
+/// instruction.
+pub fn step_until(pid: pid_t, attach: c_uint, options: c_int) -> Result<c_int> {
+    let mut signal = 0;
+    loop {
+        single_step(pid, signal)?;
 
-                PTRACE_EVENT_EXEC if options & PTRACE_O_TRACEEXEC != 0 => {
-                    return Ok(status);
-                }
+        let mut delivery = None;
+        let status = handle_signal_with(pid, attach, options, |status| {
+            delivery = Some(WSTOPSIG(status));
+            ControlFlow::Break(())
+        })?;
+        match delivery {
+            Some(pending) => signal = pending,
+            None => return Ok(status),
+        }
+    }
+}
 
-                PTRACE_EVENT_EXIT if options & PTRACE_O_TRACEEXIT != 0 => {
-                    return Ok(status);
-                }
+/// Single-steps the tracee `n` times, at least once, and returns the status
+/// of the last step. Signals are delivered as by [`step_until`], and a step
+/// into a signal handler ends the run early. Also returns early with the
+/// status of any other stop: an exit, a ptrace event, or a `SIGTRAP` not
+/// raised by the step itself, such as an `int3` or a hardware breakpoint.
+pub fn step_n(pid: pid_t, n: usize, attach: c_uint, options: c_int) -> Result<c_int> {
+    for _ in 1..n {
+        let status = step_until(pid, attach, options)?;
+        if !is_step_trap(pid, status)? {
+            return Ok(status);
+        }
+    }
 
-                PTRACE_EVENT_STOP if attach == PTRACE_SEIZE => {
-                    return Ok(status);
-                }
+    step_until(pid, attach, options)
+}
+
+fn is_step_trap(pid: pid_t, status: c_int) -> Result<bool> {
+    if !WIFSTOPPED(status) || WSTOPSIG(status) != SIGTRAP || ptrace_event(status) != 0 {
+        return Ok(false);
+    }
+
+    // Stepping over a `syscall` instruction reports `TRAP_BRKPT` rather than
+    // `TRAP_TRACE`, while an `int3` reports `SI_KERNEL`.
+    Ok(matches!(get_siginfo(pid)?.si_code, TRAP_TRACE | TRAP_BRKPT))
+}
+
+/// Resumes the tracee to its next syscall-stop, or whatever other stop comes
+/// first.
+pub fn syscall_until(pid: pid_t, attach: c_uint, options: c_int) -> Result<c_int> {
+    syscall(pid, 0)?;
+    handle_signal(pid, attach, options)
+}
+
+/// Like `handle_signal`, but every signal the loop would re-inject into the
+/// tracee is first passed to `filter`, which returns the signal to deliver
+/// instead: the same one to forward it, `0` to suppress it, or another.
+pub fn handle_signal_filtered<F>(
+    pid: pid_t,
+    attach: c_uint,
+    options: c_int,
+    mut filter: F,
+) -> Result<c_int>
+where
+    F: FnMut(c_int) -> c_int,
+{
+    let events = EventMask::from_options(options);
+    wait_reported(pid, attach, options, events, |status| {
+        ControlFlow::Continue(filter(WSTOPSIG(status)))
+    })
+}
+
+/// Like `handle_signal`, but `cb` sees the status of every stop the loop
+/// would resume past, such as the signal-delivery-stops it forwards.
+/// `ControlFlow::Continue` resumes the tracee as `handle_signal` would, and
+/// `ControlFlow::Break` returns that status instead, leaving it stopped.
+pub fn handle_signal_with<F>(pid: pid_t, attach: c_uint, options: c_int, mut cb: F) -> Result<c_int>
+where
+    F: FnMut(c_int) -> ControlFlow<()>,
+{
+    let events = EventMask::from_options(options);
+    wait_reported(pid, attach, options, events, |status| match cb(status) {
+        ControlFlow::Continue(()) => ControlFlow::Continue(WSTOPSIG(status)),
+        ControlFlow::Break(()) => ControlFlow::Break(()),
+    })
+}
+
+/// Like `handle_signal`, but fails with [`Error::WatchdogExpired`] instead of
+/// resuming the tracee past more than `max_resumes` stops it doesn't report,
+/// such as when it is flooded with signals. The tracee is left in the stop
+/// that went over the cap, whose status the error carries, with its signal
+/// not yet delivered.
+pub fn handle_signal_bounded(
+    pid: pid_t,
+    attach: c_uint,
+    options: c_int,
+    max_resumes: usize,
+) -> Result<c_int> {
+    let mut resumes = 0;
+    let mut expired = false;
+    let events = EventMask::from_options(options);
+    let status = wait_reported(pid, attach, options, events, |status| {
+        if resumes == max_resumes {
+            expired = true;
+            return ControlFlow::Break(());
+        }
+
+        resumes += 1;
+        ControlFlow::Continue(WSTOPSIG(status))
+    })?;
+
+    if expired {
+        return Err(Error::WatchdogExpired(status));
+    }
 
-                PTRACE_EVENT_SECCOMP if options & PTRACE_O_TRACESECCOMP != 0 => {
-                    return Ok(status);
+    Ok(status)
+}
+
+/// Like `handle_signal`, but only reports the ptrace events in `events`.
+/// Events the options enable that `events` leaves out are resumed past
+/// without delivering their `SIGTRAP`, so a tracer can have the kernel follow
+/// forks with `PTRACE_O_TRACEFORK` without stopping at each one. An event
+/// the options don't enable is warned about and reported, as by
+/// `handle_signal`, whatever the mask.
+pub fn handle_signal_masked(
+    pid: pid_t,
+    attach: c_uint,
+    options: c_int,
+    events: EventMask,
+) -> Result<c_int> {
+    wait_reported(pid, attach, options, events, |status| {
+        ControlFlow::Continue(WSTOPSIG(status))
+    })
+}
+
+/// Like `handle_signal`, but returns `None` once `timeout` passes without a
+/// stop to report, leaving the tracee running. `None` waits indefinitely. The
+/// tracee is polled, so a stop is noticed up to a millisecond late.
+pub fn wait_with_timeout(
+    pid: pid_t,
+    attach: c_uint,
+    options: c_int,
+    timeout: Option<Duration>,
+) -> Result<Option<c_int>> {
+    let Some(deadline) = timeout.and_then(|timeout| Instant::now().checked_add(timeout)) else {
+        return handle_signal(pid, attach, options).map(Some);
+    };
+
+    let events = EventMask::from_options(options);
+    let mut forward = |status| ControlFlow::Continue(WSTOPSIG(status));
+    loop {
+        while let Some(status) = try_wait(pid)? {
+            if let Some(status) = handle_status(pid, status, attach, options, events, &mut forward)?
+            {
+                log_reported(pid, status);
+                return Ok(Some(status));
+            }
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(None);
+        }
+        thread::sleep(POLL_INTERVAL.min(deadline - now));
+    }
+}
+
+fn wait_reported<F>(
+    pid: pid_t,
+    attach: c_uint,
+    options: c_int,
+    events: EventMask,
+    mut resume: F,
+) -> Result<c_int>
+where
+    F: FnMut(c_int) -> ControlFlow<(), c_int>,
+{
+    loop {
+        let status = waitpid(pid)?;
+        if let Some(status) = handle_status(pid, status, attach, options, events, &mut resume)? {
+            log_reported(pid, status);
+            return Ok(status);
+        }
+    }
+}
+
+/// Async version of `handle_signal`. Stops are noticed through the `SIGCHLD`
+/// the kernel sends the tracer, so the runtime needs its signal driver
+/// enabled, and the wait doesn't tie up a thread.
+#[cfg(feature = "tokio")]
+pub async fn handle_signal_async(pid: pid_t, attach: c_uint, options: c_int) -> Result<c_int> {
+    // Registered before the first poll so a stop in between isn't missed.
+    let mut sigchld = signal(SignalKind::child())?;
+    let events = EventMask::from_options(options);
+    let mut forward = |status| ControlFlow::Continue(WSTOPSIG(status));
+    loop {
+        while let Some(status) = try_wait(pid)? {
+            if let Some(status) = handle_status(pid, status, attach, options, events, &mut forward)?
+            {
+                log_reported(pid, status);
+                return Ok(status);
+            }
+        }
+
+        sigchld.recv().await;
+    }
+}
+
+fn log_reported(pid: pid_t, status: c_int) {
+    if WIFSTOPPED(status) {
+        let (signal, event) = (WSTOPSIG(status), event_name(ptrace_event(status)));
+        debug!("{pid} stopped (signal = {signal}, event = {event})");
+    } else if WIFEXITED(status) {
+        debug!("{pid} exited (code = {})", WEXITSTATUS(status));
+    } else if WIFSIGNALED(status) {
+        debug!("{pid} killed (signal = {})", WTERMSIG(status));
+    }
+}
+
+/// Returns the status `handle_signal` should report, or `None` after resuming
+/// the tracee past a stop it doesn't report. `resume` is given the status of
+/// such a stop and returns the signal to resume with, or breaks to report it
+/// after all.
+fn handle_status<F>(
+    pid: pid_t,
+    status: c_int,
+    attach: c_uint,
+    options: c_int,
+    events: EventMask,
+    resume: &mut F,
+) -> Result<Option<c_int>>
+where
+    F: FnMut(c_int) -> ControlFlow<(), c_int>,
+{
+    if WIFSTOPPED(status) {
+        let signal = WSTOPSIG(status);
+
+        match signal {
+            SIGTRAP => match ptrace_event(status) {
+                PTRACE_EVENT_STOP if attach == PTRACE_SEIZE => {
+                    return Ok(Some(status));
                 }
 
                 0 => {
@@ -89,60 +402,464 @@ pub fn handle_signal(pid: pid_t, attach: c_uint, options: c_int) -> Result<c_int
                         let si_code = siginfo.si_code;
 
                         if si_code == SIGTRAP {
-                            return Ok(SYSCALL_SIGTRAP);
+                            return Ok(Some(SYSCALL_STATUS));
                         }
 
                         if si_code == SYSCALL_SIGTRAP {
                             warn!("si_code == SYSCALL_SIGTRAP. reachable?");
-                            return Ok(SYSCALL_SIGTRAP);
+                            return Ok(Some(SYSCALL_STATUS));
                         }
                     }
-                }
 
-                _ => {
-                    warn!("unknown event (signal = {signal:?})");
-                    return Ok(status);
+                    return Ok(Some(status));
                 }
+
+                event => match EventMask::for_event(event) {
+                    Some(flag) if events.contains(flag) => return Ok(Some(status)),
+                    // Enabled by the options but masked out: resume without
+                    // the SIGTRAP.
+                    Some(flag) if EventMask::from_options(options).contains(flag) => {
+                        cont(pid, 0)?;
+                        return Ok(None);
+                    }
+                    _ => {
+                        let event = event_name(event);
+                        warn!("unknown event (signal = {signal:?}, event = {event})");
+                        return Ok(Some(status));
+                    }
+                },
             },
 
             SYSCALL_SIGTRAP if options & PTRACE_O_TRACESYSGOOD != 0 => {
-                return Ok(status);
+                return Ok(Some(status));
             }
 
             SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => {
-                match status >> 16 {
-                    PTRACE_EVENT_STOP if attach == PTRACE_SEIZE => {
-                        return Ok(status);
-                    }
-
-                    0 => {}
-
-                    _ => {
-                        warn!("unknown event (signal = {signal:?})");
-                        return Ok(status);
-                    }
+                if is_group_stop(pid, status, attach)? {
+                    return Ok(Some(status));
                 }
 
-                if attach != PTRACE_SEIZE {
-                    if let Err(err) = get_siginfo(pid) {
-                        if err.kind() == Error::from_raw_os_error(EINVAL).kind() {
-                            return Ok(status);
-                        } else {
-                            return Err(err);
-                        }
-                    }
+                if ptrace_event(status) != 0 {
+                    let event = event_name(ptrace_event(status));
+                    warn!("unknown event (signal = {signal:?}, event = {event})");
+                    return Ok(Some(status));
                 }
             }
 
             _ => {}
         }
 
+        let ControlFlow::Continue(signal) = resume(status) else {
+            return Ok(Some(status));
+        };
         cont(pid, signal)?;
+        return Ok(None);
     }
 
     if WIFEXITED(status) || WIFSIGNALED(status) {
-        return Ok(status);
+        return Ok(Some(status));
+    }
+
+    // Not expected from waitpid without WCONTINUED, but reported rather than
+    // trusted never to happen.
+    warn!("unknown status (status = {status:#x})");
+    Ok(Some(status))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitEvent {
+    Stopped { signal: c_int },
+    SyscallStop,
+    PtraceEvent { event: c_int, msg: c_ulong },
+    Exited { code: c_int },
+    Signaled { signal: c_int },
+    GroupStop { signal: c_int },
+}
+
+/// Recognises syscall-stops as reported under `PTRACE_O_TRACESYSGOOD`, which
+/// is also the form `handle_signal` returns them in without that option.
+pub fn is_syscall_stop(status: c_int) -> bool {
+    WIFSTOPPED(status) && WSTOPSIG(status) == SYSCALL_SIGTRAP
+}
+
+pub fn ptrace_event(status: c_int) -> c_int {
+    status >> 16
+}
+
+/// Names a `PTRACE_EVENT_*` value without the prefix, e.g. `"FORK"`, for
+/// logging. `0`, no event, is `"NONE"`, and values this crate doesn't know
+/// are `"UNKNOWN"`.
+pub fn event_name(event: c_int) -> &'static str {
+    match event {
+        0 => "NONE",
+        PTRACE_EVENT_FORK => "FORK",
+        PTRACE_EVENT_VFORK => "VFORK",
+        PTRACE_EVENT_CLONE => "CLONE",
+        PTRACE_EVENT_EXEC => "EXEC",
+        PTRACE_EVENT_VFORK_DONE => "VFORK_DONE",
+        PTRACE_EVENT_EXIT => "EXIT",
+        PTRACE_EVENT_SECCOMP => "SECCOMP",
+        PTRACE_EVENT_STOP => "STOP",
+        _ => "UNKNOWN",
+    }
+}
+
+pub fn exit_code(status: c_int) -> Option<c_int> {
+    WIFEXITED(status).then(|| WEXITSTATUS(status))
+}
+
+/// Returns the signal that killed the tracee. [`exit_status`] also tells
+/// whether it dumped core.
+pub fn term_signal(status: c_int) -> Option<c_int> {
+    WIFSIGNALED(status).then(|| WTERMSIG(status))
+}
+
+/// How a tracee terminated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    Code(c_int),
+    Signal { signal: c_int, core_dumped: bool },
+}
+
+/// Decodes a terminal status, returning `None` for the stops.
+pub fn exit_status(status: c_int) -> Option<Exit> {
+    if let Some(code) = exit_code(status) {
+        return Some(Exit::Code(code));
+    }
+
+    term_signal(status).map(|signal| Exit::Signal {
+        signal,
+        core_dumped: WCOREDUMP(status),
+    })
+}
+
+pub fn decode_status(pid: pid_t, status: c_int, attach: c_uint) -> Result<WaitEvent> {
+    if let Some(code) = exit_code(status) {
+        return Ok(WaitEvent::Exited { code });
+    }
+
+    if let Some(signal) = term_signal(status) {
+        return Ok(WaitEvent::Signaled { signal });
+    }
+
+    if is_syscall_stop(status) {
+        return Ok(WaitEvent::SyscallStop);
+    }
+
+    if !WIFSTOPPED(status) {
+        return Err(Error::UnexpectedStatus(status));
+    }
+
+    let signal = WSTOPSIG(status);
+    if is_group_stop(pid, status, attach)? {
+        return Ok(WaitEvent::GroupStop { signal });
+    }
+
+    match ptrace_event(status) {
+        0 => Ok(WaitEvent::Stopped { signal }),
+        event => Ok(WaitEvent::PtraceEvent {
+            event,
+            msg: get_event_msg(pid)?,
+        }),
+    }
+}
+
+/// Tells a group-stop, where the tracee should stay stopped, from the
+/// signal-delivery-stop of a stopping signal, which resumes the tracee if
+/// the signal is suppressed. Under `PTRACE_SEIZE` group-stops are reported as
+/// `PTRACE_EVENT_STOP`. Otherwise they are told apart by `PTRACE_GETSIGINFO`
+/// failing with `EINVAL`, so the tracee must still be in the stop.
+pub fn is_group_stop(pid: pid_t, status: c_int, attach: c_uint) -> Result<bool> {
+    if !WIFSTOPPED(status) || !matches!(WSTOPSIG(status), SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU) {
+        return Ok(false);
+    }
+
+    let event = ptrace_event(status);
+    if attach == PTRACE_SEIZE {
+        return Ok(event == PTRACE_EVENT_STOP);
+    }
+    if event != 0 {
+        return Ok(false);
+    }
+
+    match get_siginfo(pid) {
+        Ok(_) => Ok(false),
+        Err(err) if err.raw_os_error() == Some(EINVAL) => Ok(true),
+        Err(err) => Err(err),
+    }
+}
+
+pub fn handle_signal_event(pid: pid_t, attach: c_uint, options: c_int) -> Result<WaitEvent> {
+    let status = handle_signal(pid, attach, options)?;
+    decode_status(pid, status, attach)
+}
+
+pub fn get_siginfo(pid: pid_t) -> Result<siginfo_t> {
+    let mut siginfo = unsafe { MaybeUninit::<siginfo_t>::zeroed().assume_init() };
+    let err = unsafe { ptrace!(PTRACE_GETSIGINFO, pid, 0, &mut siginfo) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_GETSIGINFO"));
+    }
+
+    Ok(siginfo)
+}
+
+/// Returns the faulting address if the tracee is stopped on a `SIGSEGV` or
+/// `SIGBUS` raised by a fault, and `None` for any other signal, including
+/// ones sent with `kill` or `tgkill`, which carry no address.
+pub fn fault_address(pid: pid_t) -> Result<Option<u64>> {
+    let siginfo = get_siginfo(pid)?;
+    match siginfo.si_signo {
+        // Sent signals have a non-positive si_code and si_pid in place of
+        // si_addr.
+        SIGSEGV | SIGBUS if siginfo.si_code > 0 => Ok(Some(unsafe { siginfo.si_addr() } as u64)),
+        _ => Ok(None),
+    }
+}
+
+/// Resumes the single thread `pid`. ptrace works per thread, so the other
+/// threads of a traced process stay stopped until each is resumed too; see
+/// [`cont_all`].
+pub fn cont(pid: pid_t, sig: c_int) -> Result<()> {
+    let err = unsafe { ptrace!(PTRACE_CONT, pid, 0, sig) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_CONT"));
+    }
+
+    Ok(())
+}
+
+/// Resumes each of `tids` with no signal, such as every thread of a process
+/// from [`list_tasks`]. Stops at the first failure, reported as
+/// [`Error::Thread`] with the tid, leaving the remaining threads stopped.
+pub fn cont_all(tids: &[pid_t]) -> Result<()> {
+    for &tid in tids {
+        cont(tid, 0).map_err(|source| Error::Thread {
+            tid,
+            source: Box::new(source),
+        })?;
+    }
+
+    Ok(())
+}
+
+pub fn single_step(pid: pid_t, sig: c_int) -> Result<()> {
+    let err = unsafe { ptrace!(PTRACE_SINGLESTEP, pid, 0, sig) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_SINGLESTEP"));
+    }
+
+    Ok(())
+}
+
+/// Resumes the tracee until the next syscall-enter or syscall-exit stop. The
+/// kernel reports the two alternately for each syscall, so the caller has to
+/// track which one it is at; set `PTRACE_O_TRACESYSGOOD` to tell them apart
+/// from plain `SIGTRAP`s.
+pub fn syscall(pid: pid_t, sig: c_int) -> Result<()> {
+    let err = unsafe { ptrace!(PTRACE_SYSCALL, pid, 0, sig) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_SYSCALL"));
+    }
+
+    Ok(())
+}
+
+/// Returns the message of the last ptrace event: the new tid for fork, vfork
+/// and clone events, the exit status for `PTRACE_EVENT_EXIT`.
+pub fn get_event_msg(pid: pid_t) -> Result<c_ulong> {
+    let mut msg: c_ulong = 0;
+    let err = unsafe { ptrace!(PTRACE_GETEVENTMSG, pid, 0, &mut msg) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_GETEVENTMSG"));
+    }
+
+    Ok(msg)
+}
+
+/// Returns the `SECCOMP_RET_DATA` part of the `SECCOMP_RET_TRACE` action that
+/// stopped the tracee, which a filter uses to say which rule matched. Only
+/// meaningful at a `PTRACE_EVENT_SECCOMP` stop, the event message being
+/// something else at any other.
+pub fn seccomp_ret_data(pid: pid_t) -> Result<u32> {
+    Ok(get_event_msg(pid)? as u32 & SECCOMP_RET_DATA)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecInfo {
+    /// The program now running, per `/proc/<pid>/exe`.
+    pub exe: PathBuf,
+    /// The tid that called `execve`. A non-leader thread that execs takes
+    /// over the leader's tid, so this differs from `pid` in that case.
+    pub former_tid: pid_t,
+}
+
+/// At a `PTRACE_EVENT_EXEC` stop, describes the new image. Maps, symbols and
+/// breakpoints from before the exec are stale by then and should be read
+/// again. Unless the tracer has `CAP_SYS_PTRACE`, a setuid program runs
+/// without its privileges while traced, keeping the tracee's credentials.
+pub fn exec_event_info(pid: pid_t) -> Result<ExecInfo> {
+    let former_tid = get_event_msg(pid)? as pid_t;
+    let exe = fs::read_link(format!("/proc/{pid}/exe"))?;
+    Ok(ExecInfo { exe, former_tid })
+}
+
+/// At a `PTRACE_EVENT_CLONE` (or fork/vfork) stop of `pid`, fetches the new
+/// task's id and waits for its initial stop. The new task is returned
+/// stopped; resume it with `cont` like any other stop.
+pub fn follow_clone(pid: pid_t) -> Result<pid_t> {
+    let tid = get_event_msg(pid)? as pid_t;
+    let status = waitpid(tid)?;
+    if !WIFSTOPPED(status) {
+        return Err(Error::UnexpectedStatus(status));
+    }
+
+    Ok(tid)
+}
+
+/// Fails with [`Error::SuspendSeccompDenied`] if `options` include
+/// `PTRACE_O_SUSPEND_SECCOMP` and the kernel refuses it.
+pub fn set_options(pid: pid_t, options: c_int) -> Result<()> {
+    let err = unsafe { ptrace!(PTRACE_SETOPTIONS, pid, 0, options) };
+    if err == -1 {
+        return Err(Error::set_options(options));
+    }
+
+    Ok(())
+}
+
+/// Attaches with `PTRACE_ATTACH`, which sends the tracee a `SIGSTOP`; pass
+/// `PTRACE_ATTACH` as `attach` to `handle_signal` afterwards. Options such as
+/// `PTRACE_O_EXITKILL` can only be applied with `set_options` once that stop
+/// has been reported.
+pub fn attach(pid: pid_t) -> Result<()> {
+    let err = unsafe { ptrace!(PTRACE_ATTACH, pid, 0, 0) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_ATTACH"));
+    }
+
+    Ok(())
+}
+
+/// Attaches with `PTRACE_ATTACH` and waits for the stop of the attach's own
+/// `SIGSTOP`, returning the pid with its status. Signals that arrive first
+/// are delivered and the `SIGSTOP` waited for again, so the tracee is always
+/// left in that stop. `handle_signal` doesn't report it as such; continue
+/// from it with signal `0` rather than re-injecting it, or the tracee goes on
+/// to a group-stop. Fails with [`Error::UnexpectedStatus`] if the tracee dies
+/// before stopping.
+pub fn attach_and_wait(pid: pid_t) -> Result<(pid_t, c_int)> {
+    attach(pid)?;
+
+    loop {
+        let status = waitpid(pid)?;
+        if !WIFSTOPPED(status) {
+            return Err(Error::UnexpectedStatus(status));
+        }
+        if WSTOPSIG(status) == SIGSTOP {
+            return Ok((pid, status));
+        }
+
+        // No options are set yet, so this is a signal-delivery stop.
+        cont(pid, WSTOPSIG(status))?;
+    }
+}
+
+/// Attaches with `PTRACE_SEIZE`, which leaves the tracee running; pass
+/// `PTRACE_SEIZE` as `attach` to `handle_signal` afterwards.
+pub fn seize(pid: pid_t, options: c_int) -> Result<()> {
+    let err = unsafe { ptrace!(PTRACE_SEIZE, pid, 0, options) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_SEIZE"));
+    }
+
+    Ok(())
+}
+
+/// Detaches with `PTRACE_DETACH`, delivering `sig` on resume (`0` to resume
+/// cleanly, `SIGSTOP` to leave the process stopped).
+pub fn detach(pid: pid_t, sig: c_int) -> Result<()> {
+    let err = unsafe { ptrace!(PTRACE_DETACH, pid, 0, sig) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_DETACH"));
+    }
+
+    Ok(())
+}
+
+/// Stops a running tracee with `PTRACE_INTERRUPT`, which `handle_signal`
+/// reports as a `PTRACE_EVENT_STOP`. Only valid on tracees attached with
+/// `PTRACE_SEIZE`.
+pub fn interrupt(pid: pid_t) -> Result<()> {
+    let err = unsafe { ptrace!(PTRACE_INTERRUPT, pid, 0, 0) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_INTERRUPT"));
+    }
+
+    Ok(())
+}
+
+/// Restarts a seized tracee out of a group-stop without resuming it: it
+/// stays stopped, but `handle_signal` reports the `PTRACE_EVENT_STOP` it gets
+/// once a `SIGCONT` arrives, as well as other events. Answering a group-stop
+/// with `cont` instead would resume the tracee against job-control
+/// semantics; after any other kind of stop, use `cont`.
+pub fn listen(pid: pid_t) -> Result<()> {
+    let err = unsafe { ptrace!(PTRACE_LISTEN, pid, 0, 0) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_LISTEN"));
+    }
+
+    Ok(())
+}
+
+/// Issues one of the `PTRACE_PEEK*` requests, named `op` in errors. `errno`
+/// is cleared beforehand and checked rather than the return value, as -1 is
+/// a legitimate word.
+pub(crate) fn peek(request: c_uint, op: &'static str, pid: pid_t, addr: u64) -> Result<c_long> {
+    unsafe { *libc::__errno_location() = 0 };
+    let word = unsafe { ptrace!(op, request, pid, addr, 0) };
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() != Some(0) {
+        return Err(Error::ptrace_from(op, err));
+    }
+
+    Ok(word)
+}
+
+/// Reads the word at `offset` in the tracee's `struct user`.
+pub fn peek_user(pid: pid_t, offset: usize) -> Result<c_long> {
+    peek(PTRACE_PEEKUSER, "PTRACE_PEEKUSER", pid, offset as u64)
+}
+
+pub fn poke_user(pid: pid_t, offset: usize, data: c_long) -> Result<()> {
+    let err = unsafe { ptrace!(PTRACE_POKEUSER, pid, offset, data) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_POKEUSER"));
+    }
+
+    Ok(())
+}
+
+// The kernel checks the size against its own sigset_t, which is a single
+// 64-bit word rather than glibc's 1024-bit sigset_t.
+const KERNEL_SIGSET_SIZE: usize = size_of::<u64>();
+
+pub fn get_sigmask(pid: pid_t) -> Result<sigset_t> {
+    let mut mask = unsafe { MaybeUninit::<sigset_t>::zeroed().assume_init() };
+    let err = unsafe { ptrace!(PTRACE_GETSIGMASK, pid, KERNEL_SIGSET_SIZE, &mut mask) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_GETSIGMASK"));
+    }
+
+    Ok(mask)
+}
+
+pub fn set_sigmask(pid: pid_t, mask: &sigset_t) -> Result<()> {
+    let err = unsafe { ptrace!(PTRACE_SETSIGMASK, pid, KERNEL_SIGSET_SIZE, mask) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_SETSIGMASK"));
     }
 
-    unreachable!();
+    Ok(())
 }
@@ -0,0 +1,380 @@
+use std::{
+    mem::{size_of, MaybeUninit},
+    slice,
+};
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+use libc::user_regs_struct;
+use libc::{c_void, iovec, pid_t, NT_PRSTATUS, PTRACE_GETREGSET, PTRACE_SETREGSET};
+#[cfg(target_arch = "x86_64")]
+use libc::{
+    user_fpregs_struct, PTRACE_GETFPREGS, PTRACE_GETREGS, PTRACE_SETFPREGS, PTRACE_SETREGS,
+};
+
+use crate::error::{Error, Result};
+
+#[cfg(target_arch = "x86_64")]
+pub fn get_regs(pid: pid_t) -> Result<user_regs_struct> {
+    let mut regs = unsafe { MaybeUninit::<user_regs_struct>::zeroed().assume_init() };
+    let err = unsafe { ptrace!(PTRACE_GETREGS, pid, 0, &mut regs) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_GETREGS"));
+    }
+
+    Ok(regs)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn set_regs(pid: pid_t, regs: &user_regs_struct) -> Result<()> {
+    let err = unsafe { ptrace!(PTRACE_SETREGS, pid, 0, regs) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_SETREGS"));
+    }
+
+    Ok(())
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn get_fpregs(pid: pid_t) -> Result<user_fpregs_struct> {
+    let mut fpregs = unsafe { MaybeUninit::<user_fpregs_struct>::zeroed().assume_init() };
+    let err = unsafe { ptrace!(PTRACE_GETFPREGS, pid, 0, &mut fpregs) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_GETFPREGS"));
+    }
+
+    Ok(fpregs)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn set_fpregs(pid: pid_t, fpregs: &user_fpregs_struct) -> Result<()> {
+    let err = unsafe { ptrace!(PTRACE_SETFPREGS, pid, 0, fpregs) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_SETFPREGS"));
+    }
+
+    Ok(())
+}
+
+/// Saved general-purpose and floating-point registers of a tracee.
+#[cfg(target_arch = "x86_64")]
+#[derive(Clone)]
+pub struct RegsSnapshot {
+    pub(crate) regs: user_regs_struct,
+    fpregs: user_fpregs_struct,
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn save_regs(pid: pid_t) -> Result<RegsSnapshot> {
+    Ok(RegsSnapshot {
+        regs: get_regs(pid)?,
+        fpregs: get_fpregs(pid)?,
+    })
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn restore_regs(pid: pid_t, snapshot: &RegsSnapshot) -> Result<()> {
+    set_fpregs(pid, &snapshot.fpregs)?;
+    set_regs(pid, &snapshot.regs)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Register {
+    Rax,
+    Rbx,
+    Rcx,
+    Rdx,
+    Rsi,
+    Rdi,
+    Rbp,
+    Rsp,
+    R8,
+    R9,
+    R10,
+    R11,
+    R12,
+    R13,
+    R14,
+    R15,
+    Rip,
+    Eflags,
+    OrigRax,
+    Cs,
+    Ss,
+    Ds,
+    Es,
+    Fs,
+    Gs,
+    FsBase,
+    GsBase,
+}
+
+#[cfg(target_arch = "x86_64")]
+impl Register {
+    fn field(self, regs: &mut user_regs_struct) -> &mut u64 {
+        match self {
+            Self::Rax => &mut regs.rax,
+            Self::Rbx => &mut regs.rbx,
+            Self::Rcx => &mut regs.rcx,
+            Self::Rdx => &mut regs.rdx,
+            Self::Rsi => &mut regs.rsi,
+            Self::Rdi => &mut regs.rdi,
+            Self::Rbp => &mut regs.rbp,
+            Self::Rsp => &mut regs.rsp,
+            Self::R8 => &mut regs.r8,
+            Self::R9 => &mut regs.r9,
+            Self::R10 => &mut regs.r10,
+            Self::R11 => &mut regs.r11,
+            Self::R12 => &mut regs.r12,
+            Self::R13 => &mut regs.r13,
+            Self::R14 => &mut regs.r14,
+            Self::R15 => &mut regs.r15,
+            Self::Rip => &mut regs.rip,
+            Self::Eflags => &mut regs.eflags,
+            Self::OrigRax => &mut regs.orig_rax,
+            Self::Cs => &mut regs.cs,
+            Self::Ss => &mut regs.ss,
+            Self::Ds => &mut regs.ds,
+            Self::Es => &mut regs.es,
+            Self::Fs => &mut regs.fs,
+            Self::Gs => &mut regs.gs,
+            Self::FsBase => &mut regs.fs_base,
+            Self::GsBase => &mut regs.gs_base,
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn get_register(pid: pid_t, reg: Register) -> Result<u64> {
+    let mut regs = get_regs(pid)?;
+    Ok(*reg.field(&mut regs))
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn set_register(pid: pid_t, reg: Register, val: u64) -> Result<()> {
+    let mut regs = get_regs(pid)?;
+    *reg.field(&mut regs) = val;
+    set_regs(pid, &regs)
+}
+
+/// Fills `buf` with the `NT_PRSTATUS` register set and returns the number of
+/// bytes the kernel wrote, which depends on the tracee's architecture.
+pub fn get_regset(pid: pid_t, buf: &mut [u8]) -> Result<usize> {
+    let mut iov = iovec {
+        iov_base: buf.as_mut_ptr().cast::<c_void>(),
+        iov_len: buf.len(),
+    };
+
+    let err = unsafe { ptrace!(PTRACE_GETREGSET, pid, NT_PRSTATUS as usize, &mut iov) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_GETREGSET"));
+    }
+
+    Ok(iov.iov_len)
+}
+
+/// Loads the `NT_PRSTATUS` register set from `buf` and returns the number of
+/// bytes the kernel consumed.
+pub fn set_regset(pid: pid_t, buf: &[u8]) -> Result<usize> {
+    let mut iov = iovec {
+        iov_base: buf.as_ptr() as *mut c_void,
+        iov_len: buf.len(),
+    };
+
+    let err = unsafe { ptrace!(PTRACE_SETREGSET, pid, NT_PRSTATUS as usize, &mut iov) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_SETREGSET"));
+    }
+
+    Ok(iov.iov_len)
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+pub fn get_regs_regset(pid: pid_t) -> Result<user_regs_struct> {
+    let mut regs = unsafe { MaybeUninit::<user_regs_struct>::zeroed().assume_init() };
+    let buf = unsafe {
+        slice::from_raw_parts_mut(
+            (&mut regs as *mut user_regs_struct).cast::<u8>(),
+            size_of::<user_regs_struct>(),
+        )
+    };
+
+    let len = get_regset(pid, buf)?;
+    if len != size_of::<user_regs_struct>() {
+        return Err(Error::UnexpectedRegsetSize(len));
+    }
+
+    Ok(regs)
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+pub fn set_regs_regset(pid: pid_t, regs: &user_regs_struct) -> Result<()> {
+    let buf = unsafe {
+        slice::from_raw_parts(
+            (regs as *const user_regs_struct).cast::<u8>(),
+            size_of::<user_regs_struct>(),
+        )
+    };
+
+    set_regset(pid, buf)?;
+    Ok(())
+}
+
+#[cfg(target_arch = "x86_64")]
+fn pc_mut(regs: &mut user_regs_struct) -> &mut u64 {
+    &mut regs.rip
+}
+
+#[cfg(target_arch = "aarch64")]
+fn pc_mut(regs: &mut user_regs_struct) -> &mut u64 {
+    &mut regs.pc
+}
+
+#[cfg(target_arch = "x86_64")]
+fn sp_mut(regs: &mut user_regs_struct) -> &mut u64 {
+    &mut regs.rsp
+}
+
+#[cfg(target_arch = "aarch64")]
+fn sp_mut(regs: &mut user_regs_struct) -> &mut u64 {
+    &mut regs.sp
+}
+
+/// `user_regs_struct` of an i386 tracee, as `PTRACE_GETREGSET` returns it for
+/// a 32-bit process on an x86_64 kernel.
+#[cfg(target_arch = "x86_64")]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UserRegs32 {
+    pub ebx: u32,
+    pub ecx: u32,
+    pub edx: u32,
+    pub esi: u32,
+    pub edi: u32,
+    pub ebp: u32,
+    pub eax: u32,
+    pub xds: u32,
+    pub xes: u32,
+    pub xfs: u32,
+    pub xgs: u32,
+    pub orig_eax: u32,
+    pub eip: u32,
+    pub xcs: u32,
+    pub eflags: u32,
+    pub esp: u32,
+    pub xss: u32,
+}
+
+/// Register layout and syscall convention of an x86_64 tracee.
+#[cfg(target_arch = "x86_64")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Abi {
+    X86_64,
+    /// A 32-bit (compat) process.
+    I386,
+}
+
+/// Tells a 32-bit tracee from a 64-bit one by the size of its `NT_PRSTATUS`
+/// register set.
+#[cfg(target_arch = "x86_64")]
+pub fn abi(pid: pid_t) -> Result<Abi> {
+    let mut buf = [0; size_of::<user_regs_struct>()];
+    match get_regset(pid, &mut buf)? {
+        len if len == size_of::<user_regs_struct>() => Ok(Abi::X86_64),
+        len if len == size_of::<UserRegs32>() => Ok(Abi::I386),
+        len => Err(Error::UnexpectedRegsetSize(len)),
+    }
+}
+
+/// Reads the registers of a 32-bit tracee in its own layout. `get_regs` also
+/// works on such a tracee, but widens each register into the x86_64 field of
+/// the same name (`ebx` into `rbx`, `orig_eax` into `orig_rax`, ...).
+#[cfg(target_arch = "x86_64")]
+pub fn get_regs32(pid: pid_t) -> Result<UserRegs32> {
+    let mut regs = UserRegs32::default();
+    let buf = unsafe {
+        slice::from_raw_parts_mut(
+            (&mut regs as *mut UserRegs32).cast::<u8>(),
+            size_of::<UserRegs32>(),
+        )
+    };
+
+    let len = get_regset(pid, buf)?;
+    if len != size_of::<UserRegs32>() {
+        return Err(Error::UnexpectedRegsetSize(len));
+    }
+
+    Ok(regs)
+}
+
+#[cfg(target_arch = "x86_64")]
+pub fn set_regs32(pid: pid_t, regs: &UserRegs32) -> Result<()> {
+    let buf = unsafe {
+        slice::from_raw_parts(
+            (regs as *const UserRegs32).cast::<u8>(),
+            size_of::<UserRegs32>(),
+        )
+    };
+
+    let len = set_regset(pid, buf)?;
+    if len != size_of::<UserRegs32>() {
+        return Err(Error::UnexpectedRegsetSize(len));
+    }
+
+    Ok(())
+}
+
+/// Reads the program counter: `rip` on x86_64 (`eip` for an i386 tracee),
+/// `pc` on aarch64.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+pub fn get_pc(pid: pid_t) -> Result<u64> {
+    #[cfg(target_arch = "x86_64")]
+    if abi(pid)? == Abi::I386 {
+        return Ok(get_regs32(pid)?.eip.into());
+    }
+
+    Ok(*pc_mut(&mut get_regs_regset(pid)?))
+}
+
+/// Sets the program counter; for an i386 tracee, `val` is truncated to
+/// 32 bits.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+pub fn set_pc(pid: pid_t, val: u64) -> Result<()> {
+    #[cfg(target_arch = "x86_64")]
+    if abi(pid)? == Abi::I386 {
+        let mut regs = get_regs32(pid)?;
+        regs.eip = val as u32;
+        return set_regs32(pid, &regs);
+    }
+
+    let mut regs = get_regs_regset(pid)?;
+    *pc_mut(&mut regs) = val;
+    set_regs_regset(pid, &regs)
+}
+
+/// Reads the stack pointer: `rsp` on x86_64 (`esp` for an i386 tracee), `sp`
+/// on aarch64.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+pub fn get_sp(pid: pid_t) -> Result<u64> {
+    #[cfg(target_arch = "x86_64")]
+    if abi(pid)? == Abi::I386 {
+        return Ok(get_regs32(pid)?.esp.into());
+    }
+
+    Ok(*sp_mut(&mut get_regs_regset(pid)?))
+}
+
+/// Sets the stack pointer; for an i386 tracee, `val` is truncated to 32 bits.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+pub fn set_sp(pid: pid_t, val: u64) -> Result<()> {
+    #[cfg(target_arch = "x86_64")]
+    if abi(pid)? == Abi::I386 {
+        let mut regs = get_regs32(pid)?;
+        regs.esp = val as u32;
+        return set_regs32(pid, &regs);
+    }
+
+    let mut regs = get_regs_regset(pid)?;
+    *sp_mut(&mut regs) = val;
+    set_regs_regset(pid, &regs)
+}
@@ -0,0 +1,54 @@
+use libc::{c_int, pid_t, siginfo_t, SIGBUS, SIGCHLD, SIGFPE, SIGILL, SIGSEGV};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SigInfo {
+    pub signo: c_int,
+    pub code: c_int,
+    pub errno: c_int,
+    /// Faulting address, for kernel-generated `SIGSEGV`, `SIGBUS`, `SIGILL`
+    /// and `SIGFPE`.
+    pub addr: Option<u64>,
+    /// Child pid, for `SIGCHLD`.
+    pub pid: Option<pid_t>,
+    /// Child exit status or signal, for kernel-generated `SIGCHLD`.
+    pub status: Option<c_int>,
+}
+
+impl SigInfo {
+    pub fn from_raw(siginfo: siginfo_t) -> Self {
+        let signo = siginfo.si_signo;
+
+        let addr = match signo {
+            // Only kernel-generated faults carry an address; sent signals
+            // have si_pid and si_uid in its place.
+            SIGSEGV | SIGBUS | SIGILL | SIGFPE if siginfo.si_code > 0 => {
+                Some(unsafe { siginfo.si_addr() } as u64)
+            }
+            _ => None,
+        };
+
+        let (pid, status) = match signo {
+            // Kernel-generated SIGCHLDs carry a positive CLD_* code.
+            SIGCHLD if siginfo.si_code > 0 => unsafe {
+                (Some(siginfo.si_pid()), Some(siginfo.si_status()))
+            },
+            SIGCHLD => (Some(unsafe { siginfo.si_pid() }), None),
+            _ => (None, None),
+        };
+
+        Self {
+            signo,
+            code: siginfo.si_code,
+            errno: siginfo.si_errno,
+            addr,
+            pid,
+            status,
+        }
+    }
+}
+
+impl From<siginfo_t> for SigInfo {
+    fn from(siginfo: siginfo_t) -> Self {
+        Self::from_raw(siginfo)
+    }
+}
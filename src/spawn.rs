@@ -0,0 +1,73 @@
+use std::{ffi::CStr, io, iter, ptr};
+
+use libc::{
+    c_char, c_int, c_ulong, pid_t, ADDR_NO_RANDOMIZE, PTRACE_TRACEME, SIGKILL, SIGTRAP,
+    WEXITSTATUS, WIFEXITED, WIFSTOPPED, WSTOPSIG,
+};
+
+use crate::{
+    error::{Error, Result},
+    ptrace::waitpid,
+};
+
+const EXEC_FAILED: c_int = 127;
+
+/// Forks a child that requests `PTRACE_TRACEME` and `execvpe`s `path`, then
+/// waits for the exec `SIGTRAP` so the child is stopped and ready for
+/// `set_options` when this returns. `argv` and `envp` are passed as given,
+/// so `argv[0]` should normally be the program name. With `disable_aslr` the
+/// child sets `ADDR_NO_RANDOMIZE` before exec, giving it deterministic load
+/// addresses; the tracer's own personality is untouched. Any failure in the
+/// child before the exec completes is reported as [`Error::ExecFailed`].
+pub fn spawn(path: &CStr, argv: &[&CStr], envp: &[&CStr], disable_aslr: bool) -> Result<pid_t> {
+    let argv: Vec<*const c_char> = argv
+        .iter()
+        .map(|arg| arg.as_ptr())
+        .chain(iter::once(ptr::null()))
+        .collect();
+    let envp: Vec<*const c_char> = envp
+        .iter()
+        .map(|env| env.as_ptr())
+        .chain(iter::once(ptr::null()))
+        .collect();
+
+    let pid = unsafe { libc::fork() };
+    if pid == -1 {
+        return Err(Error::Io(io::Error::last_os_error()));
+    }
+
+    if pid == 0 {
+        unsafe {
+            if disable_aslr {
+                let persona = libc::personality(0xffffffff);
+                if persona == -1
+                    || libc::personality((persona | ADDR_NO_RANDOMIZE) as c_ulong) == -1
+                {
+                    libc::_exit(EXEC_FAILED);
+                }
+            }
+
+            if libc::ptrace(PTRACE_TRACEME, 0, 0, 0) != -1 {
+                libc::execvpe(path.as_ptr(), argv.as_ptr(), envp.as_ptr());
+            }
+
+            libc::_exit(EXEC_FAILED);
+        }
+    }
+
+    let status = waitpid(pid)?;
+    if WIFSTOPPED(status) && WSTOPSIG(status) == SIGTRAP {
+        return Ok(pid);
+    }
+
+    if WIFEXITED(status) && WEXITSTATUS(status) == EXEC_FAILED {
+        return Err(Error::ExecFailed);
+    }
+
+    if WIFSTOPPED(status) {
+        unsafe { libc::kill(pid, SIGKILL) };
+        waitpid(pid)?;
+    }
+
+    Err(Error::UnexpectedStatus(status))
+}
@@ -0,0 +1,117 @@
+use std::{fs, path::Path};
+
+use libc::pid_t;
+use object::{Object, ObjectSegment, ObjectSymbol};
+
+use crate::{
+    error::{Error, Result},
+    maps::{read_maps, MemoryRegion},
+};
+
+/// Returns the file-backed module mapped at `addr` together with its load base.
+fn containing_module(regions: &[MemoryRegion], addr: u64) -> Option<(&Path, u64)> {
+    let path = regions
+        .iter()
+        .find(|region| region.contains(addr))?
+        .path
+        .as_deref()
+        .filter(|path| path.is_absolute())?;
+    let base = regions
+        .iter()
+        .filter(|region| region.path.as_deref() == Some(path))
+        .map(|region| region.start)
+        .min()?;
+
+    Some((path, base))
+}
+
+/// Difference between runtime and link-time addresses of an ELF loaded at
+/// `base`: zero for non-PIE executables, `base` for most PIE and shared
+/// objects.
+fn load_bias(file: &object::File, base: u64) -> u64 {
+    let first = file
+        .segments()
+        .map(|segment| segment.address())
+        .min()
+        .unwrap_or(0);
+    base.wrapping_sub(first & !0xfff)
+}
+
+/// Resolves the runtime `addr` to the symbol containing it in the ELF mapped
+/// there, returning the symbol name and the offset into it, as in
+/// `main+0x12`. Returns `None` if `addr` isn't in a file-backed mapping, the
+/// file can't be read or isn't an ELF, or no symbol covers it; symbols
+/// without a size are taken to extend up to `addr`.
+pub fn resolve(pid: pid_t, addr: u64) -> Result<Option<(String, u64)>> {
+    let regions = read_maps(pid)?;
+    let Some((path, base)) = containing_module(&regions, addr) else {
+        return Ok(None);
+    };
+
+    // Not every mapped file is an ELF that can still be read, e.g. a data
+    // file or one since deleted.
+    let Ok(data) = fs::read(path) else {
+        return Ok(None);
+    };
+    let Ok(file) = object::File::parse(&*data) else {
+        return Ok(None);
+    };
+    let addr = addr.wrapping_sub(load_bias(&file, base));
+
+    let nearest = file
+        .symbols()
+        .chain(file.dynamic_symbols())
+        .filter(|symbol| symbol.is_definition() && symbol.address() != 0)
+        .filter(|symbol| symbol.address() <= addr)
+        .max_by_key(|symbol| symbol.address());
+
+    let Some(symbol) = nearest else {
+        return Ok(None);
+    };
+    let offset = addr - symbol.address();
+    if symbol.size() != 0 && offset >= symbol.size() {
+        return Ok(None);
+    }
+
+    let name = symbol.name().map_err(Error::Elf)?;
+    Ok(Some((name.to_owned(), offset)))
+}
+
+/// Returns the runtime address of the symbol `name`, looked up in the mapped
+/// modules in address order. Each module's `.dynsym` is searched before its
+/// `.symtab`; mapped files that can't be read or aren't ELFs are skipped.
+pub fn lookup(pid: pid_t, name: &str) -> Result<Option<u64>> {
+    let regions = read_maps(pid)?;
+    let mut seen = Vec::new();
+
+    for region in &regions {
+        let Some(path) = region.path.as_deref().filter(|path| path.is_absolute()) else {
+            continue;
+        };
+        if seen.contains(&path) {
+            continue;
+        }
+        seen.push(path);
+
+        let Some((_, base)) = containing_module(&regions, region.start) else {
+            continue;
+        };
+        // Skip mapped files that aren't readable ELFs, such as locale data.
+        let Ok(data) = fs::read(path) else {
+            continue;
+        };
+        let Ok(file) = object::File::parse(&*data) else {
+            continue;
+        };
+
+        let found = file
+            .dynamic_symbols()
+            .chain(file.symbols())
+            .find(|symbol| symbol.is_definition() && symbol.name() == Ok(name));
+        if let Some(symbol) = found {
+            return Ok(Some(symbol.address().wrapping_add(load_bias(&file, base))));
+        }
+    }
+
+    Ok(None)
+}
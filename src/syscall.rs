@@ -0,0 +1,212 @@
+#[cfg(target_arch = "x86_64")]
+use std::collections::HashMap;
+use std::mem::{size_of, MaybeUninit};
+
+#[cfg(target_arch = "x86_64")]
+use libc::ENOSYS;
+use libc::{
+    pid_t, ptrace_syscall_info, PTRACE_GET_SYSCALL_INFO, PTRACE_SYSCALL_INFO_ENTRY,
+    PTRACE_SYSCALL_INFO_EXIT, PTRACE_SYSCALL_INFO_SECCOMP,
+};
+
+use crate::error::{Error, Result};
+#[cfg(target_arch = "x86_64")]
+use crate::regs::{abi, get_regs, set_regs, Abi};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyscallOp {
+    /// The tracee is not in a syscall-stop.
+    None,
+    Entry {
+        nr: u64,
+        args: [u64; 6],
+    },
+    Exit {
+        ret: i64,
+        is_error: bool,
+    },
+    Seccomp {
+        nr: u64,
+        args: [u64; 6],
+        ret_data: u32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyscallInfo {
+    /// `AUDIT_ARCH_*` value of the syscall convention in use.
+    pub arch: u32,
+    pub instruction_pointer: u64,
+    pub stack_pointer: u64,
+    pub op: SyscallOp,
+}
+
+/// Unlike reading `orig_rax`, this tells syscall-enter and syscall-exit stops
+/// apart. Requires Linux 5.3, and `PTRACE_O_TRACESYSGOOD` for syscall-stops
+/// to be reported as such rather than as [`SyscallOp::None`].
+pub fn get_syscall_info(pid: pid_t) -> Result<SyscallInfo> {
+    let mut info = MaybeUninit::<ptrace_syscall_info>::zeroed();
+    let size = size_of::<ptrace_syscall_info>();
+    let err = unsafe { ptrace!(PTRACE_GET_SYSCALL_INFO, pid, size, info.as_mut_ptr()) };
+    if err == -1 {
+        return Err(Error::ptrace("PTRACE_GET_SYSCALL_INFO"));
+    }
+
+    let info = unsafe { info.assume_init() };
+    let op = match info.op {
+        PTRACE_SYSCALL_INFO_ENTRY => {
+            let entry = unsafe { info.u.entry };
+            SyscallOp::Entry {
+                nr: entry.nr,
+                args: entry.args,
+            }
+        }
+        PTRACE_SYSCALL_INFO_EXIT => {
+            let exit = unsafe { info.u.exit };
+            SyscallOp::Exit {
+                ret: exit.sval,
+                is_error: exit.is_error != 0,
+            }
+        }
+        PTRACE_SYSCALL_INFO_SECCOMP => {
+            let seccomp = unsafe { info.u.seccomp };
+            SyscallOp::Seccomp {
+                nr: seccomp.nr,
+                args: seccomp.args,
+                ret_data: seccomp.ret_data,
+            }
+        }
+        _ => SyscallOp::None,
+    };
+
+    Ok(SyscallInfo {
+        arch: info.arch,
+        instruction_pointer: info.instruction_pointer,
+        stack_pointer: info.stack_pointer,
+        op,
+    })
+}
+
+/// Reads the arguments of the syscall the tracee is stopped in, following
+/// the x86_64 convention (`rdi`, `rsi`, `rdx`, `r10`, `r8`, `r9`), or the
+/// i386 one (`ebx`, `ecx`, `edx`, `esi`, `edi`, `ebp`) for a 32-bit tracee.
+#[cfg(target_arch = "x86_64")]
+pub fn syscall_args(pid: pid_t) -> Result<[u64; 6]> {
+    let regs = get_regs(pid)?;
+    let args = match abi(pid)? {
+        Abi::X86_64 => [regs.rdi, regs.rsi, regs.rdx, regs.r10, regs.r8, regs.r9],
+        Abi::I386 => [regs.rbx, regs.rcx, regs.rdx, regs.rsi, regs.rdi, regs.rbp]
+            .map(|arg| arg & u64::from(u32::MAX)),
+    };
+
+    Ok(args)
+}
+
+/// Reads `orig_rax`, which also holds `orig_eax` for a 32-bit tracee. The
+/// number is looked up in that tracee's own syscall table, see [`abi`].
+#[cfg(target_arch = "x86_64")]
+pub fn syscall_number(pid: pid_t) -> Result<u64> {
+    Ok(get_regs(pid)?.orig_rax)
+}
+
+/// Replaces the return value of the syscall the tracee is stopped in. Only
+/// meaningful at the syscall-exit stop: at the entry stop the kernel has yet
+/// to run the syscall and overwrites `rax` with its own result.
+#[cfg(target_arch = "x86_64")]
+pub fn set_syscall_return(pid: pid_t, value: u64) -> Result<()> {
+    let mut regs = get_regs(pid)?;
+    regs.rax = value;
+    set_regs(pid, &regs)
+}
+
+/// Replaces the number of the syscall the tracee is about to run. Must be
+/// called at the syscall-entry stop.
+#[cfg(target_arch = "x86_64")]
+pub fn set_syscall_number(pid: pid_t, nr: u64) -> Result<()> {
+    let mut regs = get_regs(pid)?;
+    regs.orig_rax = nr;
+    set_regs(pid, &regs)
+}
+
+/// Turns the syscall the tracee is about to run into a no-op by replacing its
+/// number with -1, which the kernel skips and fails with `ENOSYS`. Call at
+/// the entry stop, then [`set_syscall_return`] at the exit stop to choose the
+/// result the tracee sees.
+#[cfg(target_arch = "x86_64")]
+pub fn cancel_syscall(pid: pid_t) -> Result<()> {
+    set_syscall_number(pid, u64::MAX)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyscallStop {
+    Enter { nr: u64, args: [u64; 6] },
+    Exit { nr: u64, retval: i64 },
+}
+
+/// Pairs up the syscall-enter and syscall-exit stops that `PTRACE_SYSCALL`
+/// alternates between, tracking for each tid which syscall it is in.
+#[cfg(target_arch = "x86_64")]
+#[derive(Debug, Default)]
+pub struct SyscallTracer {
+    in_syscall: HashMap<pid_t, u64>,
+}
+
+#[cfg(target_arch = "x86_64")]
+impl SyscallTracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Classifies the syscall-stop `pid` is in, to be called once per stop.
+    /// Uses [`get_syscall_info`] where the kernel supports it. Otherwise
+    /// falls back to the registers: the kernel enters a syscall with `rax`
+    /// set to `-ENOSYS`, so a stop with any other `rax` is an exit, even one
+    /// whose entry was never seen, such as when attaching mid-syscall. An
+    /// exit with `-ENOSYS` is only recognised by the entry before it.
+    pub fn step(&mut self, pid: pid_t) -> Result<SyscallStop> {
+        let op = get_syscall_info(pid).map(|info| info.op);
+        let stop = match op {
+            Ok(SyscallOp::Entry { nr, args }) => SyscallStop::Enter { nr, args },
+            Ok(SyscallOp::Exit { ret, .. }) => {
+                let nr = match self.in_syscall.get(&pid) {
+                    Some(&nr) => nr,
+                    None => syscall_number(pid)?,
+                };
+                SyscallStop::Exit { nr, retval: ret }
+            }
+            _ => {
+                let regs = get_regs(pid)?;
+                if !self.in_syscall.contains_key(&pid) && regs.rax == -ENOSYS as u64 {
+                    SyscallStop::Enter {
+                        nr: regs.orig_rax,
+                        args: syscall_args(pid)?,
+                    }
+                } else {
+                    let nr = self.in_syscall.get(&pid).copied();
+                    SyscallStop::Exit {
+                        nr: nr.unwrap_or(regs.orig_rax),
+                        retval: regs.rax as i64,
+                    }
+                }
+            }
+        };
+
+        match stop {
+            SyscallStop::Enter { nr, .. } => self.in_syscall.insert(pid, nr),
+            SyscallStop::Exit { .. } => self.in_syscall.remove(&pid),
+        };
+
+        Ok(stop)
+    }
+
+    pub fn in_syscall(&self, pid: pid_t) -> bool {
+        self.in_syscall.contains_key(&pid)
+    }
+
+    /// Drops the state of `pid`, for a thread that has exited or been
+    /// detached.
+    pub fn forget(&mut self, pid: pid_t) {
+        self.in_syscall.remove(&pid);
+    }
+}
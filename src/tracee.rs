@@ -0,0 +1,308 @@
+use std::{io, ops::ControlFlow, time::Duration};
+
+#[cfg(target_arch = "x86_64")]
+use libc::user_regs_struct;
+use libc::{
+    c_int, c_long, c_uint, c_ulong, pid_t, siginfo_t, sigset_t, SYS_tkill, PTRACE_SEIZE, SIGSTOP,
+    WIFSTOPPED, WSTOPSIG,
+};
+use log::warn;
+
+#[cfg(target_arch = "x86_64")]
+use crate::inject;
+use crate::{
+    error::{Error, Result},
+    mem::{self, Pod},
+    ptrace::{self, Exit, WaitEvent},
+    regs,
+    syscall::{self, SyscallInfo},
+};
+
+#[derive(Debug)]
+pub struct Tracee {
+    pid: pid_t,
+    attach: c_uint,
+    options: c_int,
+    detach_on_drop: bool,
+}
+
+impl Tracee {
+    pub fn new(pid: pid_t, attach: c_uint, options: c_int) -> Self {
+        Self {
+            pid,
+            attach,
+            options,
+            detach_on_drop: true,
+        }
+    }
+
+    pub fn pid(&self) -> pid_t {
+        self.pid
+    }
+
+    pub fn attach(&self) -> c_uint {
+        self.attach
+    }
+
+    pub fn options(&self) -> c_int {
+        self.options
+    }
+
+    /// Whether dropping the `Tracee` detaches from it with no signal, so an
+    /// early return or panic doesn't leave it stopped. A running tracee is
+    /// stopped first, as detaching needs a ptrace-stop. Enabled by default. A
+    /// tracee in a group-stop stays stopped after the detach until it gets a
+    /// `SIGCONT`.
+    pub fn set_detach_on_drop(&mut self, enable: bool) {
+        self.detach_on_drop = enable;
+    }
+
+    /// Gives up the `Tracee` without detaching, for callers that keep
+    /// tracing the pid by other means.
+    pub fn into_raw(mut self) -> pid_t {
+        self.detach_on_drop = false;
+        self.pid
+    }
+
+    /// Detaches, delivering `sig` if the tracee is in a signal-delivery stop.
+    /// A running tracee is stopped first, as on drop.
+    pub fn detach(mut self, sig: c_int) -> Result<()> {
+        self.detach_on_drop = false;
+        self.stop_and_detach(sig)
+    }
+
+    pub fn set_options(&mut self, options: c_int) -> Result<()> {
+        ptrace::set_options(self.pid, options)?;
+        self.options = options;
+        Ok(())
+    }
+
+    pub fn wait(&self) -> Result<c_int> {
+        ptrace::handle_signal(self.pid, self.attach, self.options)
+    }
+
+    pub fn resume_until(&self) -> Result<c_int> {
+        ptrace::resume_until(self.pid, self.attach, self.options)
+    }
+
+    pub fn step_until(&self) -> Result<c_int> {
+        ptrace::step_until(self.pid, self.attach, self.options)
+    }
+
+    pub fn step_n(&self, n: usize) -> Result<c_int> {
+        ptrace::step_n(self.pid, n, self.attach, self.options)
+    }
+
+    pub fn syscall_until(&self) -> Result<c_int> {
+        ptrace::syscall_until(self.pid, self.attach, self.options)
+    }
+
+    pub fn wait_with_timeout(&self, timeout: Option<Duration>) -> Result<Option<c_int>> {
+        ptrace::wait_with_timeout(self.pid, self.attach, self.options, timeout)
+    }
+
+    pub fn wait_event(&self) -> Result<WaitEvent> {
+        ptrace::handle_signal_event(self.pid, self.attach, self.options)
+    }
+
+    /// Continues the tracee, resuming it from every stop `wait` reports,
+    /// until it terminates.
+    pub fn run(&self) -> Result<Exit> {
+        loop {
+            self.cont(0)?;
+            if let Some(exit) = ptrace::exit_status(self.wait()?) {
+                return Ok(exit);
+            }
+        }
+    }
+
+    pub fn cont(&self, sig: c_int) -> Result<()> {
+        ptrace::cont(self.pid, sig)
+    }
+
+    pub fn single_step(&self, sig: c_int) -> Result<()> {
+        ptrace::single_step(self.pid, sig)
+    }
+
+    pub fn syscall(&self, sig: c_int) -> Result<()> {
+        ptrace::syscall(self.pid, sig)
+    }
+
+    pub fn interrupt(&self) -> Result<()> {
+        ptrace::interrupt(self.pid)
+    }
+
+    pub fn listen(&self) -> Result<()> {
+        ptrace::listen(self.pid)
+    }
+
+    pub fn get_siginfo(&self) -> Result<siginfo_t> {
+        ptrace::get_siginfo(self.pid)
+    }
+
+    pub fn fault_address(&self) -> Result<Option<u64>> {
+        ptrace::fault_address(self.pid)
+    }
+
+    pub fn get_event_msg(&self) -> Result<c_ulong> {
+        ptrace::get_event_msg(self.pid)
+    }
+
+    pub fn seccomp_ret_data(&self) -> Result<u32> {
+        ptrace::seccomp_ret_data(self.pid)
+    }
+
+    pub fn get_syscall_info(&self) -> Result<SyscallInfo> {
+        syscall::get_syscall_info(self.pid)
+    }
+
+    pub fn get_sigmask(&self) -> Result<sigset_t> {
+        ptrace::get_sigmask(self.pid)
+    }
+
+    pub fn set_sigmask(&self, mask: &sigset_t) -> Result<()> {
+        ptrace::set_sigmask(self.pid, mask)
+    }
+
+    pub fn peek_user(&self, offset: usize) -> Result<c_long> {
+        ptrace::peek_user(self.pid, offset)
+    }
+
+    pub fn poke_user(&self, offset: usize, data: c_long) -> Result<()> {
+        ptrace::poke_user(self.pid, offset, data)
+    }
+
+    pub fn read_memory(&self, addr: u64, buf: &mut [u8]) -> Result<usize> {
+        mem::read_memory(self.pid, addr, buf)
+    }
+
+    pub fn write_memory(&self, addr: u64, buf: &[u8]) -> Result<usize> {
+        mem::write_memory(self.pid, addr, buf)
+    }
+
+    pub fn read_memory_vm(&self, addr: u64, buf: &mut [u8]) -> Result<usize> {
+        mem::read_memory_vm(self.pid, addr, buf)
+    }
+
+    pub fn write_memory_vm(&self, addr: u64, buf: &[u8]) -> Result<usize> {
+        mem::write_memory_vm(self.pid, addr, buf)
+    }
+
+    pub fn read_val<T: Pod>(&self, addr: u64) -> Result<T> {
+        mem::read_val(self.pid, addr)
+    }
+
+    pub fn write_val<T: Pod>(&self, addr: u64, val: T) -> Result<()> {
+        mem::write_val(self.pid, addr, val)
+    }
+
+    pub fn read_cstring(&self, addr: u64, max_len: usize) -> Result<Vec<u8>> {
+        mem::read_cstring(self.pid, addr, max_len)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub fn get_regs(&self) -> Result<user_regs_struct> {
+        regs::get_regs(self.pid)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub fn set_regs(&self, regs: &user_regs_struct) -> Result<()> {
+        regs::set_regs(self.pid, regs)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub fn abi(&self) -> Result<regs::Abi> {
+        regs::abi(self.pid)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub fn save_regs(&self) -> Result<regs::RegsSnapshot> {
+        regs::save_regs(self.pid)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub fn restore_regs(&self, snapshot: &regs::RegsSnapshot) -> Result<()> {
+        regs::restore_regs(self.pid, snapshot)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub fn inject_syscall(&self, nr: u64, args: &[u64]) -> Result<u64> {
+        inject::inject_syscall(self.pid, nr, args, self.attach, self.options)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub fn get_register(&self, reg: regs::Register) -> Result<u64> {
+        regs::get_register(self.pid, reg)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub fn set_register(&self, reg: regs::Register, val: u64) -> Result<()> {
+        regs::set_register(self.pid, reg, val)
+    }
+}
+
+impl Tracee {
+    // PTRACE_DETACH fails with ESRCH on a tracee that is running, so one that
+    // is gets stopped first: with PTRACE_INTERRUPT if seized, otherwise with
+    // a SIGSTOP that the detach then replaces with `sig`.
+    fn stop_and_detach(&self, sig: c_int) -> Result<()> {
+        match ptrace::detach(self.pid, sig) {
+            Err(Error::NoSuchProcess) if is_alive(self.pid) => {}
+            result => return result,
+        }
+
+        if self.attach == PTRACE_SEIZE {
+            ptrace::interrupt(self.pid)?;
+            let status = ptrace::handle_signal(self.pid, self.attach, self.options)?;
+            if !WIFSTOPPED(status) {
+                return Ok(());
+            }
+        } else {
+            // Directed at the thread so that it is the one to take the stop.
+            if unsafe { libc::syscall(SYS_tkill, self.pid, SIGSTOP) } == -1 {
+                return Err(io::Error::last_os_error().into());
+            }
+
+            loop {
+                let mut stopped = false;
+                let status =
+                    ptrace::handle_signal_with(self.pid, self.attach, self.options, |status| {
+                        if WSTOPSIG(status) != SIGSTOP {
+                            return ControlFlow::Continue(());
+                        }
+
+                        stopped = true;
+                        ControlFlow::Break(())
+                    })?;
+                if !WIFSTOPPED(status) {
+                    return Ok(());
+                }
+                if stopped {
+                    break;
+                }
+
+                // Some other stop, such as a ptrace event, came first.
+                ptrace::cont(self.pid, 0)?;
+            }
+        }
+
+        ptrace::detach(self.pid, sig)
+    }
+}
+
+// Zombies count as alive, as they are until reaped.
+fn is_alive(pid: pid_t) -> bool {
+    unsafe { libc::kill(pid, 0) == 0 }
+}
+
+impl Drop for Tracee {
+    fn drop(&mut self) {
+        if self.detach_on_drop {
+            match self.stop_and_detach(0) {
+                Ok(()) => {}
+                Err(Error::NoSuchProcess) if !is_alive(self.pid) => {}
+                Err(err) => warn!("failed to detach from {} ({err})", self.pid),
+            }
+        }
+    }
+}
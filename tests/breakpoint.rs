@@ -0,0 +1,96 @@
+#![cfg(target_arch = "x86_64")]
+
+use std::{cell::Cell, hint::black_box, rc::Rc};
+
+use libc::{
+    pid_t, PTRACE_ATTACH, PTRACE_TRACEME, SIGSTOP, SIGTRAP, WEXITSTATUS, WIFEXITED, WIFSTOPPED,
+    WSTOPSIG,
+};
+use pdbg::{
+    breakpoint::Breakpoints, mem::read_memory, ptrace::handle_signal, regs::get_regs,
+    tracee::Tracee,
+};
+
+#[inline(never)]
+extern "C" fn skipped(x: u64) -> u64 {
+    black_box(x) + 1
+}
+
+#[inline(never)]
+extern "C" fn stopped(x: u64) -> u64 {
+    black_box(x) * 2
+}
+
+fn addr_of(func: extern "C" fn(u64) -> u64) -> u64 {
+    func as usize as u64
+}
+
+// Forks a child that stops itself for the tracer, then calls both functions
+// three times and exits.
+fn spawn_child() -> pid_t {
+    let pid = unsafe { libc::fork() };
+    assert_ne!(pid, -1);
+    if pid == 0 {
+        unsafe {
+            libc::ptrace(PTRACE_TRACEME, 0, 0, 0);
+            libc::raise(SIGSTOP);
+        }
+
+        for i in 0..3 {
+            black_box(skipped(i));
+            black_box(stopped(i));
+        }
+
+        unsafe { libc::_exit(0) };
+    }
+
+    pid
+}
+
+#[test]
+fn cont_steps_over_false_conditions_and_stops_on_true_ones() {
+    let pid = spawn_child();
+    handle_signal(pid, PTRACE_ATTACH, 0).unwrap();
+    let tracee = Tracee::new(pid, PTRACE_ATTACH, 0);
+
+    let skipped = addr_of(skipped);
+    let stopped = addr_of(stopped);
+    let mut breakpoints = Breakpoints::new();
+    breakpoints.insert(pid, skipped).unwrap();
+    breakpoints.insert(pid, stopped).unwrap();
+
+    let evaluated = Rc::new(Cell::new(0));
+    let count = Rc::clone(&evaluated);
+    breakpoints.set_condition(skipped, move |_| {
+        count.set(count.get() + 1);
+        Ok(false)
+    });
+    breakpoints.set_condition(stopped, |tracee| Ok(get_regs(tracee.pid())?.rdi >= 1));
+
+    let status = breakpoints.cont(&tracee, 0).unwrap();
+    assert!(WIFSTOPPED(status) && WSTOPSIG(status) == SIGTRAP);
+    let regs = get_regs(pid).unwrap();
+    assert_eq!((regs.rip, regs.rdi), (stopped, 1));
+    assert_eq!(breakpoints.hit_count(stopped), 1);
+    assert_eq!(breakpoints.hit_count(skipped), 0);
+    assert_eq!(evaluated.get(), 2);
+
+    // The breakpoint that was stepped over is armed again.
+    let mut byte = [0; 1];
+    read_memory(pid, skipped, &mut byte).unwrap();
+    assert_eq!(byte[0], 0xcc);
+
+    // Continuing from a reported hit steps off it rather than hitting it
+    // again.
+    let status = breakpoints.cont(&tracee, 0).unwrap();
+    assert!(WIFSTOPPED(status) && WSTOPSIG(status) == SIGTRAP);
+    let regs = get_regs(pid).unwrap();
+    assert_eq!((regs.rip, regs.rdi), (stopped, 2));
+    assert_eq!(breakpoints.hit_count(stopped), 2);
+    assert_eq!(evaluated.get(), 3);
+
+    let status = breakpoints.cont(&tracee, 0).unwrap();
+    assert!(WIFEXITED(status) && WEXITSTATUS(status) == 0);
+    assert_eq!(breakpoints.hit_count(stopped), 2);
+    assert_eq!(evaluated.get(), 3);
+}